@@ -1,6 +1,6 @@
 use {
   crate::interp::{Func, Value},
-  std::collections::HashMap,
+  indexmap::IndexMap,
 };
 
 pub type Prog = Vec<Stmt>;
@@ -17,9 +17,15 @@ pub enum BinaryOp {
   Sub,
   Mul,
   Div,
+  IntDiv,
   Mod,
   Pow,
 
+  BitAnd,
+  BitOr,
+  Shl,
+  Shr,
+
   Great,
   Less,
 
@@ -34,7 +40,7 @@ pub enum Expr {
   Literal(Value),
 
   Vec(Vec<Expr>),
-  Dict(HashMap<String, Expr>),
+  Dict(IndexMap<String, Expr>),
 
   Range { l: Box<Expr>, r: Box<Expr>, inclusive: bool },
 
@@ -60,7 +66,10 @@ pub enum Expr {
 pub enum Stmt {
   Expr(Expr),
   Cmd(Cmd),
-  Import(String),
+  Import {
+    path:  String,
+    alias: Option<String>,
+  },
   Let {
     is_exp: bool,
     name:   String,
@@ -82,12 +91,31 @@ pub enum Stmt {
     cond:    Expr,
     then_do: Box<Stmt>,
   },
-  Func(Func),
+  WithEnv {
+    env:  Expr,
+    opts: Option<Expr>,
+    body: Box<Stmt>,
+  },
+  Func {
+    func:   Func,
+    doc:    Option<String>,
+    target: Target,
+    is_exp: bool,
+  },
   Continue,
   Break,
   Return(Option<Expr>),
 }
 
+// What a function needs when it's run as a task, as in
+// `fn main(): build, 'src/*.c' -> 'main' {}`
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Target {
+  pub deps:    Vec<String>,
+  pub inputs:  Vec<Expr>,
+  pub outputs: Vec<Expr>,
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum CmdOp {
   And,
@@ -112,5 +140,7 @@ pub enum CmdOp {
 #[derive(Clone, Debug, PartialEq)]
 pub enum Cmd {
   Atom(Vec<Vec<Expr>>),
+  // Variables set only for the wrapped command, as in `FOO=bar cmd`
+  Env(Vec<(String, Vec<Expr>)>, Box<Cmd>),
   Op(Box<Cmd>, CmdOp, Box<Cmd>),
 }
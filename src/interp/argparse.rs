@@ -0,0 +1,455 @@
+use {
+  super::{value::Value, Interpreter},
+  indexmap::IndexMap,
+  itertools::Itertools,
+  std::{cell::RefCell, rc::Rc},
+};
+
+// What a script accepts, as declared in the dict passed to `argparse`
+pub struct Spec {
+  name:        String,
+  about:       Option<String>,
+  flags:       Vec<Arg>,
+  options:     Vec<Arg>,
+  positionals: Vec<Arg>,
+  commands:    Vec<(String, Spec)>,
+}
+
+struct Arg {
+  name:    String,
+  short:   Option<char>,
+  help:    Option<String>,
+  default: Value,
+  typ:     Type,
+}
+
+#[derive(Clone, Copy)]
+enum Type {
+  String,
+  Int,
+  Num,
+  Bool,
+}
+
+// Parses `args`, or the given vec of strings, according to a spec. With
+// `--help` a dict with only the help text under `help` is returned instead, for
+// the script to show before exiting
+pub fn argparse(int: &mut Interpreter, args: Vec<Value>) -> Value {
+  let spec = match args.first() {
+    Some(spec) => Spec::new("script".to_string(), spec),
+    None => panic!("expected argparse spec"),
+  };
+
+  let words = match args.get(1) {
+    Some(words) => words.clone(),
+    None if int.has_var("args") => int.get_var("args"),
+    None => Value::Vec(Rc::new(RefCell::new(Vec::new()))),
+  };
+  let words = match words {
+    Value::Vec(vec) => vec
+      .borrow()
+      .iter()
+      .map(|word| match word {
+        Value::String(word) => word.clone(),
+        _ => panic!("expected args to be strings"),
+      })
+      .collect_vec(),
+    _ => panic!("expected args to be a vec"),
+  };
+
+  match spec.parse(&words) {
+    Ok(vals) => vals,
+    Err(help) => {
+      let mut vals = IndexMap::new();
+      vals.insert("help".to_string(), Value::String(help));
+      Value::Dict(Rc::new(RefCell::new(vals)))
+    }
+  }
+}
+
+impl Spec {
+  pub fn new(name: String, val: &Value) -> Spec {
+    let dict = match val {
+      Value::Dict(dict) => dict.borrow().clone(),
+      _ => panic!("expected argparse spec to be a dict"),
+    };
+
+    let mut spec = Spec {
+      name:        dict.get("name").map_or(name, string),
+      about:       dict.get("about").map(string),
+      flags:       Vec::new(),
+      options:     Vec::new(),
+      positionals: Vec::new(),
+      commands:    Vec::new(),
+    };
+
+    for (key, val) in &dict {
+      match key.as_str() {
+        "name" | "about" => (),
+        "flags" => spec.flags = args(val, Type::Bool),
+        "options" => spec.options = args(val, Type::String),
+        "positionals" => spec.positionals = args(val, Type::String),
+        "commands" =>
+          for (name, val) in entries(val) {
+            let command = Spec::new(format!("{} {}", spec.name, name), &val);
+            spec.commands.push((name, command));
+          },
+        _ => panic!("unknown key '{}' in argparse spec", key),
+      }
+    }
+
+    spec
+  }
+
+  // The values of all the arguments, with the ones not given set to their
+  // default, or the help text when asked for it
+  pub fn parse(&self, words: &[String]) -> Result<Value, String> {
+    let mut vals = IndexMap::new();
+    for flag in &self.flags {
+      vals.insert(flag.name.clone(), Value::Bool(false));
+    }
+    for arg in self.options.iter().chain(&self.positionals) {
+      vals.insert(arg.name.clone(), arg.default.clone());
+    }
+    if !self.commands.is_empty() {
+      vals.insert("command".to_string(), Value::Nil);
+    }
+
+    let mut set = Vec::new();
+    let mut positionals = self.positionals.iter();
+    let mut only_positionals = false;
+    let mut words = words.iter();
+
+    while let Some(word) = words.next() {
+      if only_positionals
+        || word == "-"
+        || !word.starts_with('-')
+        || self.is_negative_num(word)
+      {
+        if let Some(arg) = positionals.next() {
+          vals.insert(arg.name.clone(), arg.convert(word));
+          continue;
+        }
+
+        if self.commands.is_empty() {
+          panic!("unexpected argument '{}'", word);
+        }
+
+        let command = self
+          .commands
+          .iter()
+          .find(|(name, _)| name == word)
+          .map(|(_, command)| command)
+          .unwrap_or_else(|| {
+            panic!(
+              "unknown command '{}', expected one of: {}",
+              word,
+              self.commands.iter().map(|(name, _)| name).join(", ")
+            )
+          });
+
+        let rest = words.cloned().collect_vec();
+        vals.insert("command".to_string(), Value::String(word.clone()));
+        vals.insert(word.clone(), command.parse(&rest)?);
+        break;
+      } else if word == "--" {
+        only_positionals = true;
+      } else if word == "--help" || word == "-h" {
+        return Err(self.help());
+      } else if let Some(long) = word.strip_prefix("--") {
+        let (name, val) = match long.split_once('=') {
+          Some((name, val)) => (name, Some(val.to_string())),
+          None => (long, None),
+        };
+
+        if let Some(flag) = self.flags.iter().find(|flag| flag.name == name) {
+          if val.is_some() {
+            panic!("flag '--{}' doesn't take a value", name);
+          }
+          vals.insert(flag.name.clone(), Value::Bool(true));
+        } else if let Some(option) =
+          self.options.iter().find(|option| option.name == name)
+        {
+          let val = val
+            .or_else(|| words.next().cloned())
+            .unwrap_or_else(|| panic!("option '--{}' expects a value", name));
+          self.set_option(&mut vals, &mut set, option, &val);
+        } else {
+          panic!("unexpected argument '--{}'", name);
+        }
+      } else {
+        // Short flags can be grouped as in `-vq`, and the value of a short
+        // option can follow it right away as in `-j4`
+        let shorts = &word[1..];
+        for (i, short) in shorts.char_indices() {
+          if let Some(flag) =
+            self.flags.iter().find(|flag| flag.short == Some(short))
+          {
+            vals.insert(flag.name.clone(), Value::Bool(true));
+          } else if let Some(option) =
+            self.options.iter().find(|option| option.short == Some(short))
+          {
+            let rest = &shorts[i + short.len_utf8()..];
+            let val = if rest.is_empty() {
+              words.next().cloned().unwrap_or_else(|| {
+                panic!("option '-{}' expects a value", short)
+              })
+            } else {
+              rest.trim_start_matches('=').to_string()
+            };
+            self.set_option(&mut vals, &mut set, option, &val);
+            break;
+          } else {
+            panic!("unexpected argument '-{}'", short);
+          }
+        }
+      }
+    }
+
+    if let Some(arg) = positionals.find(|arg| arg.default == Value::Nil) {
+      panic!("missing argument '<{}>'", arg.name);
+    }
+
+    Ok(Value::Dict(Rc::new(RefCell::new(vals))))
+  }
+
+  // Words like `-5` are taken as values rather than short flags, unless there's
+  // a short flag or option that's a digit
+  fn is_negative_num(&self, word: &str) -> bool {
+    let has_digit_short = self
+      .flags
+      .iter()
+      .chain(&self.options)
+      .any(|arg| arg.short.map_or(false, |short| short.is_ascii_digit()));
+
+    !has_digit_short
+      && word[1..].starts_with(|c: char| c.is_ascii_digit() || c == '.')
+      && word.parse::<f64>().is_ok()
+  }
+
+  fn set_option(
+    &self,
+    vals: &mut IndexMap<String, Value>,
+    set: &mut Vec<String>,
+    option: &Arg,
+    val: &str,
+  ) {
+    if set.contains(&option.name) {
+      panic!("option '--{}' set twice", option.name);
+    }
+    set.push(option.name.clone());
+
+    vals.insert(option.name.clone(), option.convert(val));
+  }
+
+  // Laid out like the help of `clap`, which Koi itself uses
+  pub fn help(&self) -> String {
+    let mut out = format!("{}\n", self.name);
+    if let Some(about) = &self.about {
+      out += &format!("{}\n", about);
+    }
+
+    let mut usage = vec![self.name.clone(), "[FLAGS]".to_string()];
+    if !self.options.is_empty() {
+      usage.push("[OPTIONS]".to_string());
+    }
+    for arg in &self.positionals {
+      usage.push(if arg.default == Value::Nil {
+        format!("<{}>", arg.name)
+      } else {
+        format!("[{}]", arg.name)
+      });
+    }
+    if !self.commands.is_empty() {
+      usage.push("[SUBCOMMAND]".to_string());
+    }
+    out += &format!("\nUSAGE:\n    {}\n", usage.join(" "));
+
+    let help = Arg {
+      name:    "help".to_string(),
+      short:   Some('h'),
+      help:    Some("Prints help information".to_string()),
+      default: Value::Nil,
+      typ:     Type::Bool,
+    };
+    let flags = self
+      .flags
+      .iter()
+      .chain([&help])
+      .map(|flag| (flag.switch(), flag.help_with_default()))
+      .collect_vec();
+    let options = self
+      .options
+      .iter()
+      .map(|option| {
+        (
+          format!("{} <{}>", option.switch(), option.name),
+          option.help_with_default(),
+        )
+      })
+      .collect_vec();
+    let positionals = self
+      .positionals
+      .iter()
+      .map(|arg| (format!("<{}>", arg.name), arg.help_with_default()))
+      .collect_vec();
+    let commands = self
+      .commands
+      .iter()
+      .map(|(name, command)| {
+        (name.clone(), command.about.clone().unwrap_or_default())
+      })
+      .collect_vec();
+
+    for (title, rows) in [
+      ("FLAGS", flags),
+      ("OPTIONS", options),
+      ("ARGS", positionals),
+      ("SUBCOMMANDS", commands),
+    ] {
+      if rows.is_empty() {
+        continue;
+      }
+
+      let width = rows.iter().map(|(left, _)| left.len()).max().unwrap();
+
+      out += &format!("\n{}:\n", title);
+      for (left, right) in rows {
+        let row = format!("    {:width$}    {}", left, right, width = width);
+        out += row.trim_end();
+        out += "\n";
+      }
+    }
+
+    out
+  }
+}
+
+impl Arg {
+  fn convert(&self, word: &str) -> Value {
+    let val = match self.typ {
+      Type::String => Some(Value::String(word.to_string())),
+      Type::Int => word.parse().ok().map(Value::Int),
+      Type::Num => word.parse().ok().map(Value::Num),
+      Type::Bool => word.parse().ok().map(Value::Bool),
+    };
+
+    val.unwrap_or_else(|| {
+      panic!(
+        "invalid value '{}' for '{}', expected {}",
+        word,
+        self.name,
+        self.typ.name()
+      )
+    })
+  }
+
+  // Defaults have to be of the declared type, ints being taken as nums too
+  fn check_default(&self) -> Value {
+    match (self.typ, &self.default) {
+      (_, Value::Nil)
+      | (Type::String, Value::String(_))
+      | (Type::Int, Value::Int(_))
+      | (Type::Num, Value::Num(_))
+      | (Type::Bool, Value::Bool(_)) => self.default.clone(),
+      (Type::Num, Value::Int(int)) => Value::Num(*int as f64),
+      (typ, default) => panic!(
+        "expected default of '{}' to be {}, got {}",
+        self.name,
+        typ.name(),
+        default.type_name()
+      ),
+    }
+  }
+
+  fn switch(&self) -> String {
+    match self.short {
+      Some(short) => format!("-{}, --{}", short, self.name),
+      None => format!("    --{}", self.name),
+    }
+  }
+
+  fn help_with_default(&self) -> String {
+    let help = self.help.clone().unwrap_or_default();
+
+    match &self.default {
+      Value::Nil => help,
+      default => format!("{} [default: {}]", help, default).trim().to_string(),
+    }
+  }
+}
+
+impl Type {
+  fn name(self) -> &'static str {
+    match self {
+      Type::String => "string",
+      Type::Int => "int",
+      Type::Num => "num",
+      Type::Bool => "bool",
+    }
+  }
+}
+
+// Arguments are declared as a dict from their name to either their help or a
+// dict with the `short`, `help`, `default` and `type` keys
+fn args(val: &Value, typ: Type) -> Vec<Arg> {
+  entries(val)
+    .into_iter()
+    .map(|(name, val)| {
+      let mut arg = Arg {
+        name: name.clone(),
+        short: None,
+        help: None,
+        default: Value::Nil,
+        typ,
+      };
+
+      match val {
+        Value::String(help) => arg.help = Some(help),
+        Value::Dict(dict) =>
+          for (key, val) in dict.borrow().iter() {
+            match key.as_str() {
+              "help" => arg.help = Some(string(val)),
+              "short" => {
+                let short = string(val);
+                let mut chars = short.chars();
+                arg.short = match (chars.next(), chars.next()) {
+                  (Some(short), None) => Some(short),
+                  _ => panic!("expected short of '{}' to be a letter", name),
+                };
+              }
+              "default" => arg.default = val.clone(),
+              "type" =>
+                arg.typ = match string(val).as_str() {
+                  "string" => Type::String,
+                  "int" => Type::Int,
+                  "num" => Type::Num,
+                  "bool" => Type::Bool,
+                  typ => panic!("unknown type '{}' of '{}'", typ, name),
+                },
+              _ => panic!("unknown key '{}' of '{}'", key, name),
+            }
+          },
+        _ => panic!("expected '{}' to be a string or a dict", name),
+      }
+
+      arg.default = arg.check_default();
+      arg
+    })
+    .collect()
+}
+
+fn entries(val: &Value) -> Vec<(String, Value)> {
+  match val {
+    Value::Dict(dict) =>
+      dict.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+    _ => panic!("expected argparse spec entries to be dicts"),
+  }
+}
+
+fn string(val: &Value) -> String {
+  match val {
+    Value::String(str) => str.clone(),
+    _ => panic!("expected argparse spec values to be strings"),
+  }
+}
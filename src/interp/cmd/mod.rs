@@ -2,12 +2,12 @@ use {
   super::{value::Value, Interpreter},
   crate::ast::{Cmd, CmdOp, Expr},
   either::Either,
+  indexmap::IndexMap,
   os_pipe::{pipe, PipeReader, PipeWriter},
   std::{
     cell::RefCell,
     fs::File,
     io::Read,
-    ops::DerefMut,
     process::{Child, Command, ExitStatus, Stdio},
     thread::{self, JoinHandle},
   },
@@ -15,7 +15,54 @@ use {
 
 #[cfg(test)] mod test;
 
-pub type OsEnv = Vec<(String, String)>;
+#[derive(Clone, Default)]
+pub struct OsEnv {
+  // Whether child processes start from an empty environment instead of
+  // inheriting this one
+  pub clear: bool,
+  // Variables to set, in order. `None` removes a variable
+  pub vars:  Vec<(String, Option<String>)>,
+}
+
+impl OsEnv {
+  pub fn new() -> OsEnv { OsEnv::default() }
+
+  pub fn set(&mut self, k: String, v: Option<String>) {
+    self.vars.push((k, v));
+  }
+
+  // Variables as child processes will see them, later ones overriding
+  // earlier ones
+  pub fn resolve(&self) -> IndexMap<String, String> {
+    let mut map = IndexMap::new();
+
+    for (k, v) in self.vars.iter() {
+      match v {
+        Some(v) => {
+          map.insert(k.clone(), v.clone());
+        }
+        None => {
+          map.shift_remove(k);
+        }
+      }
+    }
+
+    map
+  }
+
+  fn apply(&self, cmd: &mut Command) {
+    if self.clear {
+      cmd.env_clear();
+    }
+
+    for (k, v) in self.vars.iter() {
+      match v {
+        Some(v) => cmd.env(k, v),
+        None => cmd.env_remove(k),
+      };
+    }
+  }
+}
 
 enum Process {
   Std(Either<Command, Child>),
@@ -45,26 +92,6 @@ impl Process {
     }
   }
 
-  fn set_env(&mut self, env: OsEnv) {
-    match self {
-      Process::Std(either) => match either {
-        Either::Left(cmd) => {
-          cmd.envs(env);
-        }
-        Either::Right(_) => panic!("process already spawned"),
-      },
-      Process::Pipe { lhs, rhs } => {
-        lhs.set_env(env.clone());
-        rhs.set_env(env);
-      }
-      Process::Cond { procs, .. } => {
-        let (lhs, rhs) = procs.as_mut().unwrap().deref_mut();
-        lhs.set_env(env.clone());
-        rhs.set_env(env);
-      }
-    }
-  }
-
   fn spawn(&mut self) {
     match self {
       Process::Std(either) => match either {
@@ -126,9 +153,9 @@ impl Clone for Stream {
   }
 }
 
-impl Into<Stdio> for Stream {
-  fn into(self) -> Stdio {
-    match self {
+impl From<Stream> for Stdio {
+  fn from(val: Stream) -> Self {
+    match val {
       Stream::Inherit => Stdio::inherit(),
       Stream::Null => Stdio::null(),
       Stream::File(file) => Stdio::from(file),
@@ -139,45 +166,122 @@ impl Into<Stdio> for Stream {
 }
 
 impl Interpreter {
-  pub fn run_cmd_pipe(&mut self, cmd: Cmd, env: OsEnv) {
+  pub(crate) fn run_cmd_pipe(&mut self, cmd: Cmd, env: OsEnv) {
     let mut cmd =
-      self.build_cmd(cmd, Stream::Null, Stream::Inherit, Stream::Inherit);
-    cmd.set_env(env);
+      self.build_cmd(cmd, &env, Stream::Null, Stream::Inherit, Stream::Inherit);
     cmd.spawn();
     let rc = cmd.wait();
     self.set_rc(&rc);
   }
 
-  pub fn run_cmd_capture(
+  pub(crate) fn run_cmd_capture(
     &mut self,
     cmd: Cmd,
     env: OsEnv,
     capture_err: bool,
   ) -> String {
-    let (mut r, w) = pipe().unwrap();
+    let (r, w) = pipe().unwrap();
+    let mut err_reader = None;
 
     let err_stream = if capture_err {
       Stream::PipeWriter(w.try_clone().unwrap())
+    } else if self.err_collector.is_some() {
+      let (err_r, err_w) = pipe().unwrap();
+      err_reader = Some(drain(err_r));
+      Stream::PipeWriter(err_w)
     } else {
       Stream::Inherit
     };
 
-    let mut cmd =
-      self.build_cmd(cmd, Stream::Null, Stream::PipeWriter(w), err_stream);
-    cmd.set_env(env);
+    // Pipes are read while the command runs, as it would otherwise block once
+    // it fills them up
+    let reader = drain(r);
+
+    let mut cmd = self.build_cmd(
+      cmd,
+      &env,
+      Stream::Null,
+      Stream::PipeWriter(w),
+      err_stream,
+    );
     cmd.spawn();
     let rc = cmd.wait();
     self.set_rc(&rc);
 
-    let mut out = String::new();
-    r.read_to_string(&mut out).unwrap();
+    // The write ends of the pipes are closed only once the command is gone
+    drop(cmd);
 
-    out
+    if let Some(err_reader) = err_reader {
+      let err = err_reader.join().unwrap();
+      self.err_collector.as_mut().unwrap().push_str(&err);
+    }
+
+    reader.join().unwrap()
+  }
+
+  // Builds the variables set by a `withEnv` block. With the `clear` option
+  // commands only see the given variables, and `keep` implies `clear` but
+  // brings along the variables it lists
+  pub(crate) fn eval_env_overrides(
+    &mut self,
+    env: Expr,
+    opts: Option<Expr>,
+  ) -> OsEnv {
+    let mut overrides = OsEnv::new();
+
+    if let Some(opts) = opts {
+      let opts = match self.eval(opts) {
+        Value::Dict(opts) => opts,
+        _ => panic!("expected withEnv options to be dict"),
+      };
+
+      for (k, v) in RefCell::borrow(&opts).iter() {
+        match (k.as_ref(), v) {
+          ("clear", v) => overrides.clear |= v.is_truthy(),
+          ("keep", Value::Vec(names)) => {
+            let current = self.cmd_env().resolve();
+
+            for name in RefCell::borrow(names).iter() {
+              let name = match name {
+                Value::String(name) => name,
+                _ => panic!("expected names to keep to be strings"),
+              };
+
+              if let Some(v) = current.get(name) {
+                overrides.set(name.clone(), Some(v.clone()));
+              }
+            }
+
+            overrides.clear = true;
+          }
+          ("keep", _) => panic!("expected names to keep to be vec"),
+          (k, _) => panic!("unknown withEnv option '{}'", k),
+        }
+      }
+    }
+
+    let env = match self.eval(env) {
+      Value::Dict(env) => env,
+      _ => panic!("expected withEnv arg to be dict"),
+    };
+
+    for (k, v) in RefCell::borrow(&env).iter() {
+      // Nil removes the variable
+      let v = match v {
+        Value::Nil => None,
+        v => Some(v.to_string()),
+      };
+
+      overrides.set(k.clone(), v);
+    }
+
+    overrides
   }
 
   fn build_cmd(
     &mut self,
     cmd: Cmd,
+    env: &OsEnv,
     mut stdin: Stream,
     mut stdout: Stream,
     mut stderr: Stream,
@@ -189,12 +293,24 @@ impl Interpreter {
         let mut cmd = Command::new(segments.remove(0));
         cmd.args(segments);
 
+        env.apply(&mut cmd);
+
         cmd.stdin(stdin);
         cmd.stdout(stdout);
         cmd.stderr(stderr);
 
         Process::Std(Either::Left(cmd))
       }
+      Cmd::Env(vars, cmd) => {
+        let mut env = env.clone();
+
+        for (k, word) in vars {
+          let v = self.raster_segments(vec![word]).join(" ");
+          env.set(k, Some(v));
+        }
+
+        self.build_cmd(*cmd, &env, stdin, stdout, stderr)
+      }
       Cmd::Op(lhs, op, rhs)
         if [CmdOp::OutPipe, CmdOp::ErrPipe, CmdOp::AllPipe].contains(&op) =>
       {
@@ -208,8 +324,9 @@ impl Interpreter {
           _ => unreachable!(),
         };
 
-        let lhs = self.build_cmd(*lhs, stdin, out, err);
-        let rhs = self.build_cmd(*rhs, Stream::PipeReader(r), stdout, stderr);
+        let lhs = self.build_cmd(*lhs, env, stdin, out, err);
+        let rhs =
+          self.build_cmd(*rhs, env, Stream::PipeReader(r), stdout, stderr);
 
         Process::Pipe { lhs: Box::new(lhs), rhs: Box::new(rhs) }
       }
@@ -220,8 +337,8 @@ impl Interpreter {
         let (out_1, out_2) = (stdout.clone(), stdout);
         let (err_1, err_2) = (stderr.clone(), stderr);
 
-        let lhs = self.build_cmd(*lhs, in_1, out_1, err_1);
-        let rhs = self.build_cmd(*rhs, in_2, out_2, err_2);
+        let lhs = self.build_cmd(*lhs, env, in_1, out_1, err_1);
+        let rhs = self.build_cmd(*rhs, env, in_2, out_2, err_2);
 
         Process::Cond { op, procs: Some(Box::new((lhs, rhs))), handle: None }
       }
@@ -250,7 +367,7 @@ impl Interpreter {
           _ => unreachable!(),
         };
 
-        let file = file.open(&path).unwrap();
+        let file = file.open(path).unwrap();
 
         match op {
           CmdOp::Read => stdin = Stream::File(file),
@@ -264,7 +381,7 @@ impl Interpreter {
           _ => unreachable!(),
         }
 
-        self.build_cmd(*lhs, stdin, stdout, stderr)
+        self.build_cmd(*lhs, env, stdin, stdout, stderr)
       }
       _ => unreachable!(),
     }
@@ -282,7 +399,7 @@ impl Interpreter {
 
       vals.iter_mut().for_each(|val| {
         if let Value::String(str) = val {
-          *str = str.replace("~", &home_dir);
+          *str = str.replace('~', &home_dir);
         }
       });
 
@@ -311,11 +428,19 @@ impl Interpreter {
   fn set_rc(&mut self, rc: &ExitStatus) {
     self.get_env_mut().def(
       "rc".to_string(),
-      if let Some(rc) = rc.code() { Value::Num(rc as f64) } else { Value::Nil },
+      if let Some(rc) = rc.code() { Value::Int(rc as i64) } else { Value::Nil },
     );
   }
 }
 
+fn drain(mut r: PipeReader) -> JoinHandle<String> {
+  thread::spawn(move || {
+    let mut out = String::new();
+    r.read_to_string(&mut out).unwrap();
+    out
+  })
+}
+
 fn cross_product(mut vals: Vec<Value>) -> Vec<String> {
   let mut out = vec![String::from("")];
 
@@ -0,0 +1,163 @@
+use {
+  super::value::Value,
+  indexmap::IndexMap,
+  std::{cell::RefCell, collections::HashMap, rc::Rc},
+};
+
+// Conversions between Koi values and Rust types, for host functions to take
+// and return plain Rust values. Values of the wrong type are reported by
+// panicking, like natives do
+pub trait FromValue: Sized {
+  fn from_value(val: Value) -> Self;
+}
+
+pub trait IntoValue {
+  fn into_value(self) -> Value;
+}
+
+fn mismatch(want: &str, val: &Value) -> ! {
+  panic!("expected {}, got {}", want, val.type_name())
+}
+
+impl FromValue for Value {
+  fn from_value(val: Value) -> Self { val }
+}
+
+impl FromValue for i64 {
+  fn from_value(val: Value) -> Self {
+    match val {
+      Value::Int(int) => int,
+      val => mismatch("int", &val),
+    }
+  }
+}
+
+// Ints are widened, as Koi mixes the two freely
+impl FromValue for f64 {
+  fn from_value(val: Value) -> Self {
+    match val {
+      Value::Int(int) => int as f64,
+      Value::Num(num) => num,
+      val => mismatch("num", &val),
+    }
+  }
+}
+
+impl FromValue for bool {
+  fn from_value(val: Value) -> Self {
+    match val {
+      Value::Bool(bool) => bool,
+      val => mismatch("bool", &val),
+    }
+  }
+}
+
+impl FromValue for String {
+  fn from_value(val: Value) -> Self {
+    match val {
+      Value::String(str) => str,
+      val => mismatch("string", &val),
+    }
+  }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+  fn from_value(val: Value) -> Self {
+    match val {
+      Value::Nil => None,
+      val => Some(T::from_value(val)),
+    }
+  }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+  fn from_value(val: Value) -> Self {
+    match val {
+      Value::Vec(vec) =>
+        vec.borrow().iter().cloned().map(T::from_value).collect(),
+      val => mismatch("vec", &val),
+    }
+  }
+}
+
+impl<T: FromValue> FromValue for IndexMap<String, T> {
+  fn from_value(val: Value) -> Self {
+    match val {
+      Value::Dict(dict) => dict
+        .borrow()
+        .iter()
+        .map(|(key, val)| (key.clone(), T::from_value(val.clone())))
+        .collect(),
+      val => mismatch("dict", &val),
+    }
+  }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+  fn from_value(val: Value) -> Self {
+    IndexMap::<String, T>::from_value(val).into_iter().collect()
+  }
+}
+
+impl IntoValue for Value {
+  fn into_value(self) -> Value { self }
+}
+
+impl IntoValue for () {
+  fn into_value(self) -> Value { Value::Nil }
+}
+
+impl IntoValue for i64 {
+  fn into_value(self) -> Value { Value::Int(self) }
+}
+
+impl IntoValue for f64 {
+  fn into_value(self) -> Value { Value::Num(self) }
+}
+
+impl IntoValue for bool {
+  fn into_value(self) -> Value { Value::Bool(self) }
+}
+
+impl IntoValue for String {
+  fn into_value(self) -> Value { Value::String(self) }
+}
+
+impl IntoValue for &str {
+  fn into_value(self) -> Value { Value::String(self.to_string()) }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+  fn into_value(self) -> Value {
+    match self {
+      Some(val) => val.into_value(),
+      None => Value::Nil,
+    }
+  }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+  fn into_value(self) -> Value {
+    Value::Vec(Rc::new(RefCell::new(
+      self.into_iter().map(T::into_value).collect(),
+    )))
+  }
+}
+
+impl<T: IntoValue> IntoValue for IndexMap<String, T> {
+  fn into_value(self) -> Value {
+    Value::Dict(Rc::new(RefCell::new(
+      self.into_iter().map(|(key, val)| (key, val.into_value())).collect(),
+    )))
+  }
+}
+
+// Keys are sorted, as hash maps have no order of their own
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+  fn into_value(self) -> Value {
+    let mut entries = self.into_iter().collect::<Vec<_>>();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    entries.into_iter().collect::<IndexMap<_, _>>().into_value()
+  }
+}
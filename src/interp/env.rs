@@ -1,11 +1,14 @@
 use {
   crate::interp::{cmd::OsEnv, value::Value},
+  indexmap::IndexMap,
+  itertools::Itertools,
   std::{cell::RefCell, collections::HashMap, rc::Rc},
 };
 
+#[derive(Clone)]
 pub struct Var {
-  val:    Value,
-  is_exp: bool,
+  pub(super) val:    Value,
+  pub(super) is_exp: bool,
 }
 
 impl Var {
@@ -21,6 +24,10 @@ pub struct Env {
   parent: Option<Rc<RefCell<Env>>>,
 }
 
+impl Default for Env {
+  fn default() -> Self { Self::new() }
+}
+
 impl Env {
   pub fn new() -> Env { Env { map: HashMap::new(), parent: None } }
 
@@ -35,24 +42,53 @@ impl Env {
   pub fn get(&self, name: &str) -> Value {
     if let Some(val) = self.map.get(name) {
       val.val.clone()
+    } else if let Some(parent) = &self.parent {
+      RefCell::borrow(parent).get(name)
     } else {
-      if let Some(parent) = &self.parent {
-        RefCell::borrow(parent).get(name)
-      } else {
-        Value::Nil
+      Value::Nil
+    }
+  }
+
+  pub fn has(&self, name: &str) -> bool {
+    self.map.contains_key(name)
+      || self.parent.as_ref().map_or(false, |p| RefCell::borrow(p).has(name))
+  }
+
+  pub fn names(&self) -> Vec<String> {
+    let mut names = match &self.parent {
+      Some(parent) => RefCell::borrow(parent).names(),
+      None => Vec::new(),
+    };
+
+    for name in self.map.keys() {
+      if !names.contains(name) {
+        names.push(name.clone());
       }
     }
+
+    names
   }
 
   pub fn put(&mut self, name: &str, new_val: Value) {
     if let Some(val) = self.map.get_mut(name) {
       val.val = new_val
+    } else if let Some(parent) = &mut self.parent {
+      RefCell::borrow_mut(parent).put(name, new_val)
     } else {
-      if let Some(parent) = &mut self.parent {
-        RefCell::borrow_mut(parent).put(name, new_val)
-      } else {
-        panic!("undefined variable");
-      }
+      panic!("undefined variable");
+    }
+  }
+
+  // Sets and exports an existing variable, or defines a new exported one at
+  // the top level
+  pub fn export(&mut self, name: &str, new_val: Value) {
+    if let Some(val) = self.map.get_mut(name) {
+      val.val = new_val;
+      val.is_exp = true;
+    } else if let Some(parent) = &mut self.parent {
+      RefCell::borrow_mut(parent).export(name, new_val)
+    } else {
+      self.def(name.to_string(), Var::new(new_val, true));
     }
   }
 
@@ -60,6 +96,21 @@ impl Env {
     self.map.insert(name, var.into());
   }
 
+  // Variables defined in this scope and the ones above it, up until `root`
+  pub fn defined(&self, root: &Rc<RefCell<Env>>) -> IndexMap<String, Var> {
+    let mut vars = match &self.parent {
+      Some(parent) if !Rc::ptr_eq(parent, root) =>
+        RefCell::borrow(parent).defined(root),
+      _ => IndexMap::new(),
+    };
+
+    for name in self.map.keys().sorted() {
+      vars.insert(name.clone(), self.map[name].clone());
+    }
+
+    vars
+  }
+
   pub fn os_env(&self) -> OsEnv {
     let mut os_env = if let Some(parent) = &self.parent {
       RefCell::borrow(parent).os_env()
@@ -68,8 +119,15 @@ impl Env {
     };
 
     for (k, v) in self.map.iter() {
-      if v.is_exp {
-        os_env.push((k.clone(), v.val.to_string()));
+      // Exported functions are only meant for modules
+      if v.is_exp && !matches!(v.val, Value::Func(_)) {
+        // Exporting nil unsets the variable
+        let val = match &v.val {
+          Value::Nil => None,
+          val => Some(val.to_string()),
+        };
+
+        os_env.set(k.clone(), val);
       }
     }
 
@@ -10,6 +10,11 @@ use {
   },
 };
 
+pub type NativeFn = fn(&mut Interpreter, Vec<Value>) -> Value;
+
+// Natives as stored in values, which can also be closures registered by hosts
+pub type HostFn = Rc<dyn Fn(&mut Interpreter, Vec<Value>) -> Value>;
+
 #[derive(Clone)]
 pub enum Func {
   User {
@@ -21,7 +26,7 @@ pub enum Func {
   Native {
     name:     String,
     params:   Option<usize>,
-    func:     fn(&mut Interpreter, Vec<Value>) -> Value,
+    func:     HostFn,
     receiver: Option<Box<Value>>,
   },
 }
@@ -0,0 +1,275 @@
+use {
+  super::{
+    native::{self, compare, to_f64},
+    num,
+    value::Value,
+    Interpreter,
+  },
+  std::{
+    cell::RefCell,
+    f64::consts,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+  },
+};
+
+// Small xorshift64* generator, good enough for scripting and reproducible
+// across platforms once seeded
+pub struct Rng {
+  state: u64,
+}
+
+impl Rng {
+  pub fn new() -> Rng {
+    let nanos = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_nanos() as u64)
+      .unwrap_or(0);
+
+    Rng::from_seed(nanos)
+  }
+
+  pub fn from_seed(seed: u64) -> Rng {
+    // Mix the seed so that small seeds don't produce similar sequences, the
+    // state must never be zero
+    let mut state = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    state = (state ^ (state >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    state = (state ^ (state >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    state ^= state >> 31;
+
+    Rng { state: if state == 0 { 1 } else { state } }
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    self.state ^= self.state >> 12;
+    self.state ^= self.state << 25;
+    self.state ^= self.state >> 27;
+    self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+  }
+
+  // Uniform float in [0, 1)
+  fn next_f64(&mut self) -> f64 {
+    (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+  }
+
+  // Uniform integer in [0, bound)
+  fn below(&mut self, bound: u64) -> u64 {
+    // Reject the values that would bias the result towards small numbers
+    let zone = u64::MAX - u64::MAX % bound;
+
+    loop {
+      let n = self.next_u64();
+      if n < zone {
+        return n % bound;
+      }
+    }
+  }
+}
+
+impl Default for Rng {
+  fn default() -> Self { Self::new() }
+}
+
+pub fn module() -> Value {
+  let mut math = native::module(&[
+    ("sqrt", Some(1), sqrt),
+    ("cbrt", Some(1), cbrt),
+    ("exp", Some(1), exp),
+    ("log", None, log),
+    ("log2", Some(1), log2),
+    ("log10", Some(1), log10),
+    ("sin", Some(1), sin),
+    ("cos", Some(1), cos),
+    ("tan", Some(1), tan),
+    ("asin", Some(1), asin),
+    ("acos", Some(1), acos),
+    ("atan", Some(1), atan),
+    ("atan2", Some(2), atan2),
+    ("hypot", Some(2), hypot),
+    ("min", None, min),
+    ("max", None, max),
+    ("clamp", Some(3), clamp),
+    ("seed", Some(1), seed),
+    ("random", Some(0), random),
+    ("randInt", Some(2), rand_int),
+    ("shuffle", Some(1), shuffle),
+    ("choice", Some(1), choice),
+  ]);
+
+  math.insert("pi".to_string(), Value::Num(consts::PI));
+  math.insert("e".to_string(), Value::Num(consts::E));
+  math.insert("inf".to_string(), Value::Num(f64::INFINITY));
+
+  Value::Dict(Rc::new(RefCell::new(math)))
+}
+
+fn num_arg(val: &Value) -> f64 {
+  match val {
+    Value::Int(_) | Value::Num(_) => to_f64(val),
+    _ => panic!("expected arg to be num"),
+  }
+}
+
+fn int_arg(val: &Value) -> i64 {
+  num::whole(val).expect("expected arg to be int")
+}
+
+fn unary(args: Vec<Value>, f: fn(f64) -> f64) -> Value {
+  Value::Num(f(num_arg(&args[0])))
+}
+
+pub fn sqrt(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  unary(args, f64::sqrt)
+}
+
+pub fn cbrt(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  unary(args, f64::cbrt)
+}
+
+pub fn exp(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  unary(args, f64::exp)
+}
+
+pub fn log(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  match &args[..] {
+    [x] => Value::Num(num_arg(x).ln()),
+    [x, base] => Value::Num(num_arg(x).log(num_arg(base))),
+    _ => panic!("expected 1 or 2 args"),
+  }
+}
+
+pub fn log2(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  unary(args, f64::log2)
+}
+
+pub fn log10(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  unary(args, f64::log10)
+}
+
+pub fn sin(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  unary(args, f64::sin)
+}
+
+pub fn cos(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  unary(args, f64::cos)
+}
+
+pub fn tan(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  unary(args, f64::tan)
+}
+
+pub fn asin(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  unary(args, f64::asin)
+}
+
+pub fn acos(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  unary(args, f64::acos)
+}
+
+pub fn atan(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  unary(args, f64::atan)
+}
+
+pub fn atan2(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  Value::Num(num_arg(&args[0]).atan2(num_arg(&args[1])))
+}
+
+pub fn hypot(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  Value::Num(num_arg(&args[0]).hypot(num_arg(&args[1])))
+}
+
+// Both `min(1, 2, 3)` and `min([1, 2, 3])` are accepted
+fn num_list(args: Vec<Value>) -> Vec<Value> {
+  let list = match &args[..] {
+    [Value::Vec(vec)] => RefCell::borrow(vec).clone(),
+    _ => args,
+  };
+
+  for val in list.iter() {
+    num_arg(val);
+  }
+
+  list
+}
+
+pub fn min(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  num_list(args).into_iter().min_by(compare).unwrap_or(Value::Nil)
+}
+
+pub fn max(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  num_list(args).into_iter().max_by(compare).unwrap_or(Value::Nil)
+}
+
+pub fn clamp(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  let (x, lo, hi) = (&args[0], &args[1], &args[2]);
+
+  if num_arg(lo) > num_arg(hi) {
+    panic!("expected lower bound to be at most upper bound");
+  }
+
+  if num_arg(x) < num_arg(lo) {
+    lo.clone()
+  } else if num_arg(x) > num_arg(hi) {
+    hi.clone()
+  } else {
+    x.clone()
+  }
+}
+
+pub fn seed(int: &mut Interpreter, args: Vec<Value>) -> Value {
+  int.rng = Rng::from_seed(int_arg(&args[0]) as u64);
+  Value::Nil
+}
+
+pub fn random(int: &mut Interpreter, _args: Vec<Value>) -> Value {
+  Value::Num(int.rng.next_f64())
+}
+
+pub fn rand_int(int: &mut Interpreter, args: Vec<Value>) -> Value {
+  let (from, to) = (int_arg(&args[0]), int_arg(&args[1]));
+
+  if from > to {
+    panic!("expected lower bound to be at most upper bound");
+  }
+
+  // Both bounds are inclusive
+  let span = (to as i128 - from as i128 + 1) as u128;
+  let offset = if span > u64::MAX as u128 {
+    int.rng.next_u64()
+  } else {
+    int.rng.below(span as u64)
+  };
+
+  Value::Int((from as i128 + offset as i128) as i64)
+}
+
+pub fn shuffle(int: &mut Interpreter, args: Vec<Value>) -> Value {
+  let vec = if let Value::Vec(vec) = &args[0] {
+    vec
+  } else {
+    panic!("expected arg to be vec")
+  };
+  let mut vec = RefCell::borrow_mut(vec);
+
+  // Fisher-Yates, in place
+  for i in (1..vec.len()).rev() {
+    let j = int.rng.below(i as u64 + 1) as usize;
+    vec.swap(i, j);
+  }
+
+  Value::Nil
+}
+
+pub fn choice(int: &mut Interpreter, args: Vec<Value>) -> Value {
+  let vec = if let Value::Vec(vec) = &args[0] {
+    RefCell::borrow(vec)
+  } else {
+    panic!("expected arg to be vec")
+  };
+
+  if vec.is_empty() {
+    return Value::Nil;
+  }
+
+  vec[int.rng.below(vec.len() as u64) as usize].clone()
+}
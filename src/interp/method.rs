@@ -1,190 +1,148 @@
-use super::{func::Func, native, value::Value, Interpreter};
+use {
+  super::{
+    func::{Func, NativeFn},
+    native,
+    value::Value,
+    Interpreter,
+  },
+  itertools::Itertools,
+  std::rc::Rc,
+};
+
+// The kinds of values a method can be called on
+#[derive(Clone, Copy)]
+enum Recv {
+  Any,
+  String,
+  Vec,
+  Dict,
+  Num,
+}
+
+// All native methods, with the number of params they take. The first one
+// that matches a value and a name is the one called
+const METHODS: &[(Recv, &str, NativeFn, Option<usize>)] = &[
+  (Recv::Any, "string", native::string, Some(1)),
+  (Recv::Any, "type", native::typ, Some(1)),
+  (Recv::Any, "bool", native::bool, Some(1)),
+  (Recv::Any, "toJson", native::to_json, Some(1)),
+  (Recv::String, "parseJson", native::parse_json, Some(1)),
+  (Recv::String, "strip", native::strip, Some(1)),
+  (Recv::String, "contains", native::string_contains, Some(2)),
+  (Recv::String, "lower", native::lower, Some(1)),
+  (Recv::String, "upper", native::upper, Some(1)),
+  (Recv::String, "parseBool", native::parse_bool, Some(1)),
+  (Recv::String, "parseNum", native::parse_num, Some(1)),
+  (Recv::String, "replace", native::replace, Some(3)),
+  (Recv::String, "split", native::split, Some(2)),
+  (Recv::String, "join", native::join, Some(2)),
+  (Recv::String, "matches", native::matches, Some(2)),
+  (Recv::String, "find", native::find, Some(2)),
+  (Recv::String, "len", native::string_len, Some(1)),
+  (Recv::Vec, "len", native::vec_len, Some(1)),
+  (Recv::Dict, "len", native::dict_len, Some(1)),
+  (Recv::Vec, "map", native::map, Some(2)),
+  (Recv::Vec, "filter", native::filter, Some(2)),
+  (Recv::Vec, "forEach", native::for_each, Some(2)),
+  (Recv::Vec, "clone", native::clone_vec, Some(1)),
+  (Recv::Dict, "clone", native::clone_dict, Some(1)),
+  (Recv::Vec, "toDict", native::vec_2_dict, Some(1)),
+  (Recv::Dict, "toVec", native::dict_2_vec, Some(1)),
+  (Recv::Vec, "contains", native::vec_contains, Some(2)),
+  (Recv::Dict, "contains", native::dict_contains, Some(2)),
+  (Recv::Vec, "remove", native::vec_remove, Some(2)),
+  (Recv::Dict, "remove", native::dict_remove, Some(2)),
+  (Recv::Vec, "push", native::vec_push, Some(2)),
+  (Recv::Vec, "pop", native::vec_pop, Some(1)),
+  (Recv::Vec, "insert", native::vec_insert, Some(3)),
+  (Recv::Vec, "extend", native::vec_extend, Some(2)),
+  (Recv::Vec, "sort", native::sort, Some(1)),
+  (Recv::Vec, "sortBy", native::sort_by, Some(2)),
+  (Recv::Vec, "reverse", native::reverse, Some(1)),
+  (Recv::Vec, "reduce", native::reduce, Some(3)),
+  (Recv::Vec, "find", native::vec_find, Some(2)),
+  (Recv::Vec, "any", native::any, Some(2)),
+  (Recv::Vec, "all", native::all, Some(2)),
+  (Recv::Vec, "indexOf", native::index_of, Some(2)),
+  (Recv::Vec, "unique", native::unique, Some(1)),
+  (Recv::Vec, "flatten", native::flatten, Some(1)),
+  (Recv::Vec, "zip", native::zip, Some(2)),
+  (Recv::Vec, "enumerate", native::enumerate, Some(1)),
+  (Recv::Vec, "chunks", native::chunks, Some(2)),
+  (Recv::Vec, "min", native::vec_min, Some(1)),
+  (Recv::Vec, "max", native::vec_max, Some(1)),
+  (Recv::Vec, "sum", native::sum, Some(1)),
+  (Recv::Vec, "join", native::vec_join, Some(2)),
+  (Recv::Dict, "keys", native::keys, Some(1)),
+  (Recv::Dict, "values", native::values, Some(1)),
+  (Recv::Dict, "get", native::dict_get, Some(3)),
+  (Recv::Dict, "setDefault", native::set_default, Some(3)),
+  (Recv::Dict, "merge", native::merge, Some(2)),
+  (Recv::Dict, "filter", native::dict_filter, Some(2)),
+  (Recv::Dict, "map", native::dict_map, Some(2)),
+  (Recv::Dict, "pick", native::pick, Some(2)),
+  (Recv::Dict, "omit", native::omit, Some(2)),
+  (Recv::Num, "round", native::round, None),
+  (Recv::Num, "floor", native::floor, Some(1)),
+  (Recv::Num, "ceil", native::ceil, Some(1)),
+  (Recv::Num, "abs", native::abs, Some(1)),
+  (Recv::Num, "toFixed", native::to_fixed, Some(2)),
+  (Recv::Num, "toString", native::num_to_string, None),
+  (Recv::Num, "isInteger", native::is_integer, Some(1)),
+];
 
 impl Interpreter {
-  pub fn build_native_method(&self, base: Value, method_name: String) -> Value {
-    let func = match (base.clone(), &method_name[..]) {
-      (_, "string") => Func::Native {
-        func:     native::string,
-        params:   Some(1),
-        name:     "string".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (_, "type") => Func::Native {
-        func:     native::typ,
-        params:   Some(1),
-        name:     "type".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (_, "bool") => Func::Native {
-        func:     native::bool,
-        params:   Some(1),
-        name:     "bool".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (_, "toJson") => Func::Native {
-        func:     native::to_json,
-        params:   Some(1),
-        name:     "toJson".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "parseJson") => Func::Native {
-        func:     native::parse_json,
-        params:   Some(1),
-        name:     "parseJson".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "strip") => Func::Native {
-        func:     native::strip,
-        params:   Some(1),
-        name:     "strip".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "contains") => Func::Native {
-        func:     native::string_contains,
-        params:   Some(2),
-        name:     "contains".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "lower") => Func::Native {
-        func:     native::lower,
-        params:   Some(1),
-        name:     "lower".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "upper") => Func::Native {
-        func:     native::upper,
-        params:   Some(1),
-        name:     "upper".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "parseBool") => Func::Native {
-        func:     native::parse_bool,
-        params:   Some(1),
-        name:     "parseBool".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "parseNum") => Func::Native {
-        func:     native::parse_num,
-        params:   Some(1),
-        name:     "parseNum".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "replace") => Func::Native {
-        func:     native::replace,
-        params:   Some(3),
-        name:     "replace".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "split") => Func::Native {
-        func:     native::split,
-        params:   Some(2),
-        name:     "split".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "join") => Func::Native {
-        func:     native::join,
-        params:   Some(2),
-        name:     "join".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "matches") => Func::Native {
-        func:     native::matches,
-        params:   Some(2),
-        name:     "matches".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "find") => Func::Native {
-        func:     native::find,
-        params:   Some(2),
-        name:     "find".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::String(_), "len") => Func::Native {
-        func:     native::string_len,
-        params:   Some(1),
-        name:     "len".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::Vec(_), "len") => Func::Native {
-        func:     native::vec_len,
-        params:   Some(1),
-        name:     "len".to_string(),
-        receiver: Some(Box::new(base)),
+  pub(crate) fn build_native_method(
+    &self,
+    base: Value,
+    method_name: String,
+  ) -> Value {
+    let (func, params) = match native_method(&base, &method_name) {
+      Some(method) => method,
+      None => match base {
+        Value::Dict(_) =>
+          panic!("dict has no key or method named '{}'", method_name),
+        _ => panic!("no method found with this name"),
       },
-      (Value::Dict(_), "len") => Func::Native {
-        func:     native::dict_len,
-        params:   Some(1),
-        name:     "len".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::Vec(_), "map") => Func::Native {
-        func:     native::map,
-        params:   Some(2),
-        name:     "map".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::Vec(_), "filter") => Func::Native {
-        func:     native::filter,
-        params:   Some(2),
-        name:     "filter".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::Vec(_), "forEach") => Func::Native {
-        func:     native::for_each,
-        params:   Some(2),
-        name:     "forEach".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::Vec(_), "clone") => Func::Native {
-        func:     native::clone_vec,
-        params:   Some(1),
-        name:     "clone".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::Dict(_), "clone") => Func::Native {
-        func:     native::clone_dict,
-        params:   Some(1),
-        name:     "clone".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::Vec(_), "toDict") => Func::Native {
-        func:     native::vec_2_dict,
-        params:   Some(1),
-        name:     "toDict".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::Dict(_), "toVec") => Func::Native {
-        func:     native::dict_2_vec,
-        params:   Some(1),
-        name:     "toVec".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::Vec(_), "contains") => Func::Native {
-        func:     native::vec_contains,
-        params:   Some(2),
-        name:     "contains".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::Dict(_), "contains") => Func::Native {
-        func:     native::dict_contains,
-        params:   Some(2),
-        name:     "contains".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::Vec(_), "remove") => Func::Native {
-        func:     native::vec_remove,
-        params:   Some(2),
-        name:     "remove".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      (Value::Dict(_), "remove") => Func::Native {
-        func:     native::dict_remove,
-        params:   Some(2),
-        name:     "remove".to_string(),
-        receiver: Some(Box::new(base)),
-      },
-      _ => panic!("no method found with this name"),
     };
-    Value::Func(func)
+
+    Value::Func(Func::Native {
+      func: Rc::new(func),
+      params,
+      name: method_name,
+      receiver: Some(Box::new(base)),
+    })
+  }
+}
+
+pub fn method_names(base: &Value) -> Vec<&'static str> {
+  METHODS
+    .iter()
+    .filter(|(recv, ..)| recv.matches(base))
+    .map(|(_, name, ..)| *name)
+    .unique()
+    .collect()
+}
+
+fn native_method(
+  base: &Value,
+  method_name: &str,
+) -> Option<(NativeFn, Option<usize>)> {
+  METHODS
+    .iter()
+    .find(|(recv, name, ..)| *name == method_name && recv.matches(base))
+    .map(|(_, _, func, params)| (*func, *params))
+}
+
+impl Recv {
+  fn matches(self, base: &Value) -> bool {
+    matches!(
+      (self, base),
+      (Recv::Any, _)
+        | (Recv::String, Value::String(_))
+        | (Recv::Vec, Value::Vec(_))
+        | (Recv::Dict, Value::Dict(_))
+        | (Recv::Num, Value::Int(_) | Value::Num(_))
+    )
   }
 }
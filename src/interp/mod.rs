@@ -1,35 +1,89 @@
 use {
   crate::{
     ast::{BinaryOp, Expr, Prog, Stmt, UnaryOp},
-    interp::env::{Env, Var},
+    interp::{
+      cmd::OsEnv,
+      env::{Env, Var},
+    },
     lexer::new as new_lexer,
-    parser,
+    parser::Parser,
   },
+  indexmap::IndexMap,
   std::{
+    any::Any,
     cell::{Ref, RefCell, RefMut},
-    collections::HashMap,
     env as std_env,
     fmt::Debug,
-    mem,
-    path::PathBuf,
+    fs, mem,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
     rc::Rc,
+    sync::Arc,
   },
 };
-pub use {func::Func, value::Value};
+pub use {
+  convert::{FromValue, IntoValue},
+  func::Func,
+  method::method_names,
+  snapshot::Snapshot,
+  value::Value,
+};
 
+mod argparse;
 mod cmd;
+mod convert;
 mod env;
 mod func;
+mod math;
 mod method;
+mod module;
 mod native;
+mod num;
+mod snapshot;
 mod value;
 
-#[cfg(test)] mod test;
+#[cfg(test)]
+#[allow(noop_method_call)]
+mod test;
 
 pub struct Interpreter {
   env:         Rc<RefCell<Env>>,
+  // Natives and OS variables, which are shared by all modules
+  builtins:    Rc<RefCell<Env>>,
   collector:   Option<String>,
   import_root: PathBuf,
+  rng:         math::Rng,
+
+  // Error output of commands, collected apart from the rest when set
+  err_collector: Option<String>,
+
+  // Files that have been imported, and the ones being imported right now
+  modules:   IndexMap<PathBuf, module::Module>,
+  importing: Vec<PathBuf>,
+
+  // Variables set by the `withEnv` blocks being run, innermost last, along
+  // with the exported variables as they were when each block started
+  env_overrides: Vec<(OsEnv, IndexMap<String, String>)>,
+
+  // The snapshot being restored, while only declarations are run
+  restoring: Option<Arc<Snapshot>>,
+}
+
+// What a panic can leave half changed, to be put back before running more
+pub(crate) struct State {
+  env:           Rc<RefCell<Env>>,
+  import_root:   PathBuf,
+  importing:     Vec<PathBuf>,
+  env_overrides: Vec<(OsEnv, IndexMap<String, String>)>,
+}
+
+// The message a panic was raised with, as errors are raised by panicking
+pub(crate) fn panic_message(err: Box<dyn Any + Send>) -> String {
+  err
+    .downcast_ref::<&str>()
+    .map(|msg| msg.to_string())
+    .or_else(|| err.downcast_ref::<String>().cloned())
+    .unwrap_or_else(|| "unknown error".to_string())
 }
 
 #[derive(Debug)]
@@ -39,12 +93,28 @@ enum Escape {
   Return(Value),
 }
 
+impl Default for Interpreter {
+  fn default() -> Self { Self::new() }
+}
+
 impl Interpreter {
   pub fn new() -> Interpreter {
+    let env = Rc::new(RefCell::new(Env::new()));
     let mut interpreter = Interpreter {
-      env:         Rc::new(RefCell::new(Env::new())),
+      env:         Rc::clone(&env),
+      builtins:    env,
       collector:   None,
       import_root: std::env::current_dir().expect("couldn't get working dir"),
+      rng:         math::Rng::new(),
+
+      err_collector: None,
+
+      modules:   IndexMap::new(),
+      importing: Vec::new(),
+
+      env_overrides: Vec::new(),
+
+      restoring: None,
     };
     interpreter.init_native_funcs();
     interpreter.import_os_env();
@@ -52,65 +122,178 @@ impl Interpreter {
     interpreter
   }
 
-  pub fn run(&mut self, prog: Prog) {
+  pub(crate) fn run(&mut self, prog: Prog) {
     for stmt in prog.into_iter() {
+      // The values of everything else come from the snapshot
+      if self.restoring.is_some() {
+        let restore = match &stmt {
+          Stmt::Func { .. } | Stmt::Import { .. } => true,
+          Stmt::Let { name, .. } => self.is_uncopied(name),
+          _ => false,
+        };
+        if !restore {
+          continue;
+        }
+      }
+
       self.run_stmt(stmt).expect("escape bubbled up to top level");
     }
   }
 
+  // Runs a script, with imports relative to the working directory
+  pub fn run_source(&mut self, source: &str) {
+    self.run(Parser::new(new_lexer(source.to_string())).parse());
+  }
+
+  // Runs a file, with imports relative to it
+  pub fn run_file<P: AsRef<Path>>(&mut self, path: P) {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).unwrap_or_else(|err| {
+      panic!("couldn't read '{}': {}", path.display(), err)
+    });
+
+    let path = fs::canonicalize(path).expect("couldn't set import root");
+    self.set_import_root(path.parent().unwrap().to_path_buf());
+
+    self.run_entry(path, Parser::new(new_lexer(source)).parse());
+  }
+
+  // Evaluates a single expression, as in `x.len() * 2`
+  pub fn eval_source(&mut self, source: &str) -> Value {
+    let expr = Parser::new(new_lexer(source.to_string())).parse_single_expr();
+    self.eval(expr)
+  }
+
+  // Calls a function in scope, which is usually one declared by a script
+  pub fn call_fn(&mut self, name: &str, args: Vec<Value>) -> Value {
+    if !self.has_var(name) {
+      panic!("no function named '{}'", name);
+    }
+
+    let func = self.get_var(name);
+    self.call(func, args)
+  }
+
+  // Like `run_source` and `eval_source`, but errors are returned rather than
+  // raised, with the interpreter left in the scope it was in before
+  pub fn try_run(&mut self, source: &str) -> Result<(), String> {
+    self.try_with_state(|int| int.run_source(source))
+  }
+
+  pub fn try_eval(&mut self, source: &str) -> Result<Value, String> {
+    self.try_with_state(|int| int.eval_source(source))
+  }
+
+  fn try_with_state<T>(
+    &mut self,
+    f: impl FnOnce(&mut Interpreter) -> T,
+  ) -> Result<T, String> {
+    let state = self.save_state();
+
+    panic::catch_unwind(AssertUnwindSafe(|| f(self))).map_err(|err| {
+      self.restore_state(state);
+      panic_message(err)
+    })
+  }
+
+  pub(crate) fn save_state(&self) -> State {
+    State {
+      env:           Rc::clone(&self.env),
+      import_root:   self.import_root.clone(),
+      importing:     self.importing.clone(),
+      env_overrides: self.env_overrides.clone(),
+    }
+  }
+
+  // Goes back to the scope and imports that were current when the state was
+  // saved. Variables defined in that scope since then are kept
+  pub(crate) fn restore_state(&mut self, state: State) {
+    self.env = state.env;
+    self.import_root = state.import_root;
+    self.importing = state.importing;
+    self.env_overrides = state.env_overrides;
+  }
+
+  // Output is printed unless collected, for it to be taken as a string
   pub fn do_collect(&mut self) { self.collector = Some(String::new()); }
 
-  fn init_native_funcs(&mut self) {
-    use native::*;
+  // Output collected so far, which is then started over
+  pub fn take_collected(&mut self) -> String {
+    self.collector.replace(String::new()).unwrap_or_default()
+  }
 
-    self.get_env_mut().def(
-      "print".to_string(),
-      Value::Func(Func::Native {
-        name:     "print".to_string(),
-        params:   None,
-        func:     print,
-        receiver: None,
-      }),
-    );
+  // Error output is collected too, apart from the rest
+  pub(crate) fn do_collect_err(&mut self) {
+    self.err_collector = Some(String::new());
+  }
 
-    self.get_env_mut().def(
-      "input".to_string(),
-      Value::Func(Func::Native {
-        name:     "input".to_string(),
-        params:   None,
-        func:     input,
-        receiver: None,
-      }),
-    );
+  pub(crate) fn take_collected_err(&mut self) -> String {
+    self.err_collector.replace(String::new()).unwrap_or_default()
+  }
 
-    self.get_env_mut().def(
-      "exit".to_string(),
-      Value::Func(Func::Native {
-        name:     "exit".to_string(),
-        params:   Some(1),
-        func:     exit,
-        receiver: None,
-      }),
-    );
+  // Lets scripts call a function of the host by the given name, from whatever
+  // module. Functions with a number of params are only called with that many
+  // arguments
+  pub fn register_fn<F, R>(
+    &mut self,
+    name: &str,
+    params: Option<usize>,
+    func: F,
+  ) where
+    F: Fn(Vec<Value>) -> R + 'static,
+    R: IntoValue,
+  {
+    self.def_fn(name, params, move |_, args| func(args));
+  }
 
-    self.get_env_mut().def(
-      "glob".to_string(),
-      Value::Func(Func::Native {
-        name:     "glob".to_string(),
-        params:   Some(1),
-        func:     glob,
-        receiver: None,
+  // Defines a native function along with the builtin ones
+  pub(crate) fn def_fn<F, R>(
+    &mut self,
+    name: &str,
+    params: Option<usize>,
+    func: F,
+  ) where
+    F: Fn(&mut Interpreter, Vec<Value>) -> R + 'static,
+    R: IntoValue,
+  {
+    let func = Func::Native {
+      name: name.to_string(),
+      params,
+      func: Rc::new(move |int: &mut Interpreter, args| {
+        func(int, args).into_value()
       }),
-    );
+      receiver: None,
+    };
+
+    self.builtins.borrow_mut().def(name.to_string(), Value::Func(func));
+  }
+
+  fn init_native_funcs(&mut self) {
+    use native::*;
+
+    self.def_fn("print", None, print);
+    self.def_fn("input", None, input);
+    self.def_fn("exit", Some(1), exit);
+    self.def_fn("glob", Some(1), glob);
+    self.def_fn("argparse", None, argparse::argparse);
+
+    self.get_env_mut().def("math".to_string(), math::module());
+    self.get_env_mut().def("fs".to_string(), fs_module());
+    self.get_env_mut().def("path".to_string(), path_module());
+    self.get_env_mut().def("env".to_string(), env_module());
   }
 
-  pub fn set_import_root(&mut self, import_root: PathBuf) {
+  pub(crate) fn set_import_root(&mut self, import_root: PathBuf) {
     self.import_root = import_root;
   }
 
+  // OS variables are imported as exported variables, so that changing them
+  // also changes them for spawned commands. It also makes `cmd_env` the whole
+  // environment of commands, which `env.get`, `env.all` and `keep` rely on.
+  // Unchanged ones are passed with the value commands would inherit anyway
   fn import_os_env(&mut self) {
     for (k, v) in std_env::vars() {
-      RefCell::borrow_mut(&self.env).def(k, Value::String(v));
+      RefCell::borrow_mut(&self.env).def(k, Var::new(Value::String(v), true));
     }
   }
 
@@ -123,6 +306,36 @@ impl Interpreter {
     self.env = parent_env;
   }
 
+  // Variables passed to spawned commands
+  fn cmd_env(&self) -> OsEnv {
+    let script_env = self.get_env().os_env();
+    let current = script_env.resolve();
+    let mut env = script_env;
+
+    for (overrides, before) in self.env_overrides.iter() {
+      if overrides.clear {
+        env = overrides.clone();
+      } else {
+        env.vars.extend(overrides.vars.iter().cloned());
+      }
+
+      // Variables exported inside the block are set after the block's own,
+      // so that clearing doesn't lose them
+      for (k, v) in current.iter() {
+        if before.get(k) != Some(v) {
+          env.set(k.clone(), Some(v.clone()));
+        }
+      }
+      for k in before.keys() {
+        if !current.contains_key(k) {
+          env.set(k.clone(), None);
+        }
+      }
+    }
+
+    env
+  }
+
   fn get_env(&self) -> Ref<Env> { RefCell::borrow(&self.env) }
 
   fn get_env_mut(&mut self) -> RefMut<Env> { RefCell::borrow_mut(&self.env) }
@@ -130,10 +343,11 @@ impl Interpreter {
   fn run_stmt(&mut self, stmt: Stmt) -> Result<(), Escape> {
     match stmt {
       Stmt::Cmd(cmd) => {
-        let env = self.get_env().os_env();
+        let env = self.cmd_env();
 
         if self.collector.is_some() {
-          let output = self.run_cmd_capture(cmd, env, true);
+          let capture_err = self.err_collector.is_none();
+          let output = self.run_cmd_capture(cmd, env, capture_err);
           self.collector.as_mut().unwrap().push_str(&output);
         } else {
           self.run_cmd_pipe(cmd, env);
@@ -148,24 +362,7 @@ impl Interpreter {
         self.push_env();
         self.get_env_mut().def(name, Var::new(val, is_exp));
       }
-      Stmt::Import(mut base_path) => {
-        if !base_path.ends_with(".koi") {
-          base_path.push_str(".koi");
-        }
-
-        let mut path = self.import_root.clone();
-        path.push(&base_path);
-
-        let source = std::fs::read_to_string(&path)
-          .expect("reading imported file's content");
-        let prog = parser::Parser::new(new_lexer(source)).parse();
-
-        path.pop();
-
-        let old_import_root = std::mem::replace(&mut self.import_root, path);
-        self.run(prog);
-        self.import_root = old_import_root;
-      }
+      Stmt::Import { path, alias } => self.import(path, alias),
       Stmt::Expr(expr) => {
         self.eval(expr);
       }
@@ -199,10 +396,10 @@ impl Interpreter {
             );
 
             self.push_env();
-            self.get_env_mut().def(lvar.clone(), Value::Num(l as f64));
+            self.get_env_mut().def(lvar.clone(), Value::Int(l as i64));
 
             for i in l..r {
-              self.get_env_mut().put(&lvar, Value::Num(i as f64));
+              self.get_env_mut().put(&lvar, Value::Int(i as i64));
 
               let res = self.run_stmt(*each_do.clone());
               match &res {
@@ -223,7 +420,7 @@ impl Interpreter {
             self.get_env_mut().def(rvar.clone(), Value::Nil);
 
             for (i, v) in RefCell::borrow(&vec).iter().enumerate() {
-              self.get_env_mut().put(&lvar, Value::Num(i as f64));
+              self.get_env_mut().put(&lvar, Value::Int(i as i64));
               self.get_env_mut().put(&rvar, v.clone());
 
               let res = self.run_stmt(*each_do.clone());
@@ -271,6 +468,16 @@ impl Interpreter {
           };
         }
       }
+      Stmt::WithEnv { env, opts, body } => {
+        let overrides = self.eval_env_overrides(env, opts);
+        let before = self.get_env().os_env().resolve();
+
+        self.env_overrides.push((overrides, before));
+        let res = self.run_stmt(*body);
+        self.env_overrides.pop();
+
+        res?;
+      }
       Stmt::If { cond, then_do, else_do } =>
         if self.eval(cond).is_truthy() {
           self.run_stmt(*then_do)?;
@@ -279,7 +486,7 @@ impl Interpreter {
         },
       Stmt::Continue => return Err(Escape::Continue),
       Stmt::Break => return Err(Escape::Break),
-      Stmt::Func(func) => {
+      Stmt::Func { func, is_exp, .. } => {
         match func {
           // Lambdas don't get parsed as Stmt::Func but Expr::Lambda, therefore
           // a name should always be present
@@ -290,7 +497,7 @@ impl Interpreter {
               body,
               captured_env: Some(Rc::clone(&self.env)),
             });
-            self.get_env_mut().def(name.unwrap(), func);
+            self.get_env_mut().def(name.unwrap(), Var::new(func, is_exp));
           }
           Func::Native { .. } => unreachable!(),
         }
@@ -318,13 +525,13 @@ impl Interpreter {
         let dict = dict
           .into_iter()
           .map(|(key, expr)| (key, self.eval(expr)))
-          .collect::<HashMap<String, Value>>();
+          .collect::<IndexMap<String, Value>>();
         let dict = Rc::new(RefCell::new(dict));
         Value::Dict(dict)
       }
       Expr::Cmd(cmd) => {
-        let os_env = self.get_env().os_env();
-        Value::String(self.run_cmd_capture(cmd, os_env, false))
+        let env = self.cmd_env();
+        Value::String(self.run_cmd_capture(cmd, env, false))
       }
       Expr::Get(name) => RefCell::borrow(&self.env).get(&name).clone(),
       Expr::GetField { base, index } => {
@@ -337,18 +544,17 @@ impl Interpreter {
             let vec = &vec[from..to];
             Some(Value::Vec(Rc::new(RefCell::new(vec.to_vec()))))
           }
-          (Value::Vec(vec), Value::Num(index)) => {
-            let index = if index.trunc() == index {
-              index as usize
-            } else {
-              panic!("expected integer index")
-            };
-
-            RefCell::borrow(&vec).get(index).cloned()
-          }
+          (Value::Vec(vec), index @ (Value::Int(_) | Value::Num(_))) =>
+            match num::whole(&index) {
+              Some(index) if index >= 0 =>
+                RefCell::borrow(&vec).get(index as usize).cloned(),
+              _ => panic!("expected non-negative integer index"),
+            },
           (
             Value::Dict(dict),
-            index @ Value::String(_) | index @ Value::Num(_),
+            index @ Value::String(_)
+            | index @ Value::Int(_)
+            | index @ Value::Num(_),
           ) => {
             let index = dict_key(index);
 
@@ -360,10 +566,11 @@ impl Interpreter {
         if let Some(val) = val {
           val
         } else {
-          let method_name = if let Value::String(method_name) = index {
-            method_name
-          } else {
-            panic!("expected string index");
+          let method_name = match (&base, index) {
+            (_, Value::String(method_name)) => method_name,
+            (Value::Dict(_), index) =>
+              panic!("dict has no key named '{}'", dict_key(index)),
+            _ => panic!("expected string index"),
           };
 
           self.build_native_method(base, method_name)
@@ -380,18 +587,17 @@ impl Interpreter {
         let value = self.eval(*expr);
 
         match (base, index) {
-          (Value::Vec(vec), Value::Num(index)) => {
-            let index = if index.trunc() == index {
-              index as usize
-            } else {
-              panic!("expected integer index")
-            };
-
-            vec.borrow_mut()[index] = value.clone();
-          }
+          (Value::Vec(vec), index) => match num::whole(&index) {
+            Some(index) if index >= 0 => {
+              vec.borrow_mut()[index as usize] = value.clone();
+            }
+            _ => panic!("expected non-negative integer index"),
+          },
           (
             Value::Dict(dict),
-            index @ Value::String(_) | index @ Value::Num(_),
+            index @ Value::String(_)
+            | index @ Value::Int(_)
+            | index @ Value::Num(_),
           ) => {
             let index = dict_key(index);
 
@@ -419,17 +625,18 @@ impl Interpreter {
         let l = self.eval(*l);
         let r = self.eval(*r);
 
-        match (l, r) {
-          // The x.trunc() == x part is to check that the numbers are integers
-          (Value::Num(l), Value::Num(r))
-            if l.trunc() == l && r.trunc() == r =>
+        match (num::whole(&l), num::whole(&r)) {
+          (Some(l), Some(r)) if l >= 0 && r >= 0 =>
             Value::Range(l as usize, r as usize + if inclusive { 1 } else { 0 }),
-          _ => panic!("range must evaluate to integers"),
+          _ => panic!("range must evaluate to non-negative integers"),
         }
       }
       Expr::Binary(lhs, BinaryOp::Sum, rhs) => {
         match (self.eval(*lhs), self.eval(*rhs)) {
-          (Value::Num(lhs), Value::Num(rhs)) => Value::Num(lhs + rhs),
+          (
+            lhs @ (Value::Int(_) | Value::Num(_)),
+            rhs @ (Value::Int(_) | Value::Num(_)),
+          ) => num::arith(BinaryOp::Sum, lhs, rhs),
           (Value::String(lhs), Value::String(rhs)) => Value::String(lhs + &rhs),
           (Value::Vec(lhs), Value::Vec(rhs)) => {
             let lhs = RefCell::borrow(&lhs);
@@ -445,7 +652,7 @@ impl Interpreter {
             let lhs = RefCell::borrow(&lhs);
             let rhs = RefCell::borrow(&rhs);
 
-            let mut res = HashMap::new();
+            let mut res = IndexMap::new();
             for (k, v) in lhs.clone().into_iter() {
               res.insert(k, v);
             }
@@ -463,29 +670,18 @@ impl Interpreter {
           BinaryOp::Sub,
           BinaryOp::Mul,
           BinaryOp::Div,
+          BinaryOp::IntDiv,
           BinaryOp::Mod,
           BinaryOp::Pow,
+          BinaryOp::BitAnd,
+          BinaryOp::BitOr,
+          BinaryOp::Shl,
+          BinaryOp::Shr,
           BinaryOp::Less,
           BinaryOp::Great,
         ]
         .contains(&op) =>
-      {
-        let (lhs, rhs) = match (self.eval(*lhs), self.eval(*rhs)) {
-          (Value::Num(lhs), Value::Num(rhs)) => (lhs, rhs),
-          _ => panic!("invalid operands types for op {:?}", op),
-        };
-
-        match op {
-          BinaryOp::Sub => Value::Num(lhs - rhs),
-          BinaryOp::Mul => Value::Num(lhs * rhs),
-          BinaryOp::Div => Value::Num(lhs / rhs),
-          BinaryOp::Mod => Value::Num(lhs % rhs),
-          BinaryOp::Pow => Value::Num(lhs.powf(rhs)),
-          BinaryOp::Less => Value::Bool(lhs < rhs),
-          BinaryOp::Great => Value::Bool(lhs > rhs),
-          _ => unreachable!(),
-        }
-      }
+        num::arith(op, self.eval(*lhs), self.eval(*rhs)),
       Expr::Binary(lhs, BinaryOp::And, rhs) => {
         let lhs = self.eval(*lhs);
         if lhs.is_truthy() { self.eval(*rhs) } else { lhs }
@@ -498,15 +694,7 @@ impl Interpreter {
         Value::Bool(self.eval(*lhs) == self.eval(*rhs)),
       Expr::Unary(UnaryOp::Not, expr) =>
         Value::Bool(!self.eval(*expr).is_truthy()),
-      Expr::Unary(UnaryOp::Neg, expr) => {
-        let num = if let Value::Num(num) = self.eval(*expr) {
-          num
-        } else {
-          panic!("invalid operand type for op {:?}", UnaryOp::Neg);
-        };
-
-        Value::Num(-num)
-      }
+      Expr::Unary(UnaryOp::Neg, expr) => num::neg(self.eval(*expr)),
       Expr::Call { func, args } => {
         let func = self.eval(*func);
         let args: Vec<Value> =
@@ -582,11 +770,24 @@ impl Interpreter {
     }
   }
 
-  pub fn set_args(&mut self, args: Vec<String>) {
+  pub(crate) fn eval_expr(&mut self, expr: Expr) -> Value { self.eval(expr) }
+
+  pub fn get_var(&self, name: &str) -> Value { self.get_env().get(name) }
+
+  pub fn has_var(&self, name: &str) -> bool { self.get_env().has(name) }
+
+  pub fn set_var(&mut self, name: &str, val: Value) {
+    self.get_env_mut().def(name.to_string(), val);
+  }
+
+  // Names of all the variables in scope
+  pub(crate) fn var_names(&self) -> Vec<String> { self.get_env().names() }
+
+  pub(crate) fn set_args(&mut self, args: Vec<String>) {
     self.get_env_mut().def(
       "args".to_string(),
       Value::Vec(Rc::new(RefCell::new(
-        args.into_iter().map(|s| Value::String(s)).collect(),
+        args.into_iter().map(Value::String).collect(),
       ))),
     );
   }
@@ -595,6 +796,7 @@ impl Interpreter {
 fn dict_key(val: Value) -> String {
   match val {
     Value::String(str) => str,
+    Value::Int(int) => int.to_string(),
     Value::Num(num) => num.to_string(),
     _ => panic!("expected num or string"),
   }
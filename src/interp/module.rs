@@ -0,0 +1,209 @@
+use {
+  crate::{
+    ast::Prog,
+    interp::{
+      env::{Env, Var},
+      Interpreter, Value,
+    },
+    lexer::new as new_lexer,
+    parser::Parser,
+  },
+  indexmap::IndexMap,
+  itertools::Itertools,
+  std::{
+    cell::RefCell,
+    env, fs, mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+  },
+};
+
+// Modules written in Koi that come with the binary, imported as `std/...`
+const STD_MODULES: &[(&str, &str)] = &[
+  ("std/log.koi", include_str!("std/log.koi")),
+  ("std/retry.koi", include_str!("std/retry.koi")),
+  ("std/semver.koi", include_str!("std/semver.koi")),
+];
+
+// A file that has been run, which is kept around so that it runs only once no
+// matter how many times it's imported. What it declared are the variables of
+// `env` up until `root`, the scope it was run in
+pub struct Module {
+  env:     Rc<RefCell<Env>>,
+  root:    Rc<RefCell<Env>>,
+  exports: Value,
+}
+
+impl Interpreter {
+  // Without a name files run in the current scope, so that they see what the
+  // importing file declared, like `args`, and everything they declare is
+  // brought into it. With a name they run in a scope of their own and only
+  // what they export ends up in a dict with that name
+  pub(crate) fn import(&mut self, mut path: String, alias: Option<String>) {
+    if !path.ends_with(".koi") {
+      path.push_str(".koi");
+    }
+
+    let path = self.find_import(&path);
+
+    if !self.modules.contains_key(&path) {
+      let module = self.load_module(path.clone(), alias.is_none());
+      self.modules.insert(path.clone(), module);
+
+      if alias.is_none() {
+        return;
+      }
+    }
+    let module = &self.modules[&path];
+
+    match alias {
+      // Files already run somewhere else only bring in what they declared
+      None => {
+        let vars = RefCell::borrow(&module.env).defined(&module.root);
+        for (name, var) in vars {
+          self.get_env_mut().def(name, var);
+        }
+      }
+      Some(alias) => {
+        let exports = module.exports.clone();
+        self.get_env_mut().def(alias, exports);
+      }
+    }
+  }
+
+  // Paths starting with `.` are relative to the importing file, others are
+  // also looked for in the directories listed in `KOI_PATH`, in `~/.koi/lib`
+  // and in the `lib/koi` directory installed along with Koi. Paths starting
+  // with `std/` are reserved for the standard library
+  fn find_import(&self, path: &str) -> PathBuf {
+    if path.starts_with("std/") {
+      if std_source(Path::new(path)).is_none() {
+        panic!("no module named '{}' in the standard library", path);
+      }
+      return PathBuf::from(path);
+    }
+
+    let mut dirs = vec![self.import_root.clone()];
+
+    if !path.starts_with('.') {
+      if let Some(koi_path) = env::var_os("KOI_PATH") {
+        dirs.extend(env::split_paths(&koi_path));
+      }
+
+      if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join(".koi").join("lib"));
+      }
+
+      // As in `/usr/local/lib/koi` for `/usr/local/bin/koi`
+      if let Ok(exe) = env::current_exe() {
+        if let Some(prefix) = exe.parent().and_then(Path::parent) {
+          dirs.push(prefix.join("lib").join("koi"));
+        }
+      }
+    }
+
+    dirs
+      .iter()
+      .map(|dir| dir.join(path))
+      .find(|path| path.is_file())
+      .and_then(|path| fs::canonicalize(path).ok())
+      .unwrap_or_else(|| {
+        panic!(
+          "couldn't find '{}' to import, looked in: {}",
+          path,
+          dirs.iter().map(|dir| dir.display()).join(", ")
+        )
+      })
+  }
+
+  // Runs the file a script starts from. It counts as being imported while it
+  // runs, so that importing it back is a cycle, and as imported afterwards
+  pub(crate) fn run_entry(&mut self, path: PathBuf, prog: Prog) {
+    let root = Rc::clone(&self.env);
+
+    self.importing.push(path.clone());
+    self.run(prog);
+    self.importing.pop();
+
+    let module = Module::new(Rc::clone(&self.env), root);
+    self.modules.insert(path, module);
+  }
+
+  fn load_module(&mut self, path: PathBuf, in_current_scope: bool) -> Module {
+    if self.importing.contains(&path) {
+      panic!(
+        "import cycle: {} -> {}",
+        self.importing.iter().map(|path| display_path(path)).join(" -> "),
+        display_path(&path)
+      );
+    }
+
+    // The standard library imports relative to the importing file, as its
+    // modules aren't anywhere on disk
+    let (source, import_root) = match std_source(&path) {
+      Some(source) => (source.to_string(), self.import_root.clone()),
+      None => (
+        fs::read_to_string(&path).unwrap_or_else(|err| {
+          panic!("couldn't import '{}': {}", path.display(), err)
+        }),
+        path.parent().unwrap().to_path_buf(),
+      ),
+    };
+    let prog = Parser::new(new_lexer(source)).parse();
+
+    let root = if in_current_scope {
+      Rc::clone(&self.env)
+    } else {
+      Rc::clone(&self.builtins)
+    };
+    let module_env = Rc::new(RefCell::new(Env::new_from(&root)));
+
+    let old_env = mem::replace(&mut self.env, module_env);
+    let old_import_root = mem::replace(&mut self.import_root, import_root);
+    self.restore_module_vars(&path);
+    self.importing.push(path);
+
+    self.run(prog);
+
+    self.importing.pop();
+    self.import_root = old_import_root;
+    // In the current scope the file's declarations stay visible afterwards
+    let module_env = if in_current_scope {
+      Rc::clone(&self.env)
+    } else {
+      mem::replace(&mut self.env, old_env)
+    };
+
+    Module::new(module_env, root)
+  }
+}
+
+impl Module {
+  fn new(env: Rc<RefCell<Env>>, root: Rc<RefCell<Env>>) -> Module {
+    let exports = RefCell::borrow(&env)
+      .defined(&root)
+      .into_iter()
+      .filter(|(_, var)| var.is_exp)
+      .map(|(name, var)| (name, var.val))
+      .collect();
+
+    Module { env, root, exports: Value::Dict(Rc::new(RefCell::new(exports))) }
+  }
+
+  pub fn declared(&self) -> IndexMap<String, Var> {
+    RefCell::borrow(&self.env).defined(&self.root)
+  }
+}
+
+fn std_source(path: &Path) -> Option<&'static str> {
+  STD_MODULES
+    .iter()
+    .find(|(name, _)| Path::new(name) == path)
+    .map(|(_, source)| *source)
+}
+
+// Paths in the working directory are shown relative to it
+fn display_path(path: &Path) -> String {
+  let cwd = env::current_dir().unwrap_or_default();
+  path.strip_prefix(&cwd).unwrap_or(path).display().to_string()
+}
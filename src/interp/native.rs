@@ -1,15 +1,26 @@
 use {
-  super::{value::Value, Interpreter},
-  crate::interp::dict_key,
+  super::{
+    func::{Func, NativeFn},
+    num,
+    value::Value,
+    Interpreter,
+  },
+  crate::{ast::BinaryOp, interp::dict_key},
+  indexmap::IndexMap,
   itertools::Itertools,
   regex::Regex,
   serde_json::{from_str as json_from_str, Value as JSONValue},
   std::{
     cell::RefCell,
-    collections::HashMap,
+    cmp::Ordering,
+    ffi::OsStr,
+    fs,
     io::{self, BufRead, Write},
+    path::{Component, Path, PathBuf},
     process,
     rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    time::{SystemTime, UNIX_EPOCH},
   },
 };
 
@@ -18,7 +29,7 @@ pub fn print(int: &mut Interpreter, args: Vec<Value>) -> Value {
 
   if let Some(str) = &mut int.collector {
     str.push_str(&res);
-    str.push_str("\n");
+    str.push('\n');
   } else {
     println!("{}", res);
   }
@@ -48,8 +59,8 @@ pub fn input(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
 }
 
 pub fn exit(_: &mut Interpreter, mut args: Vec<Value>) -> Value {
-  let code = match args.remove(0) {
-    Value::Num(num) if num.trunc() == num => num as i32,
+  let code = match num::whole(&args.remove(0)) {
+    Some(int) => int as i32,
     _ => panic!("expected integer"),
   };
 
@@ -85,16 +96,7 @@ pub fn bool(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
 }
 
 pub fn typ(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
-  Value::String(String::from(match args.remove(0) {
-    Value::Nil => "nil",
-    Value::Num(_) => "num",
-    Value::String(_) => "string",
-    Value::Bool(_) => "bool",
-    Value::Vec(_) => "vec",
-    Value::Dict(_) => "dict",
-    Value::Range(_, _) => "range",
-    Value::Func(_) => "func",
-  }))
+  Value::String(args.remove(0).type_name().to_string())
 }
 
 pub fn to_json(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
@@ -169,7 +171,10 @@ pub fn parse_num(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
   } else {
     unreachable!()
   };
-  Value::Num(recv.parse::<f64>().unwrap())
+  match recv.parse::<i64>() {
+    Ok(int) => Value::Int(int),
+    Err(_) => Value::Num(recv.parse::<f64>().unwrap()),
+  }
 }
 
 pub fn replace(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
@@ -228,21 +233,21 @@ pub fn string_len(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
   } else {
     unreachable!()
   };
-  Value::Num(recv.len() as f64)
+  Value::Int(recv.len() as i64)
 }
 
 pub fn vec_len(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
   let recv =
     if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
   let recv = RefCell::borrow(&recv);
-  Value::Num(recv.len() as f64)
+  Value::Int(recv.len() as i64)
 }
 
 pub fn dict_len(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
   let recv =
     if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
   let recv = RefCell::borrow(&recv);
-  Value::Num(recv.len() as f64)
+  Value::Int(recv.len() as i64)
 }
 
 pub fn map(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
@@ -309,11 +314,11 @@ pub fn vec_2_dict(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
     if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
   let recv = RefCell::borrow(&recv);
 
-  let mut map = HashMap::new();
+  let mut map = IndexMap::new();
 
   for tuple in recv.iter() {
     let tuple = if let Value::Vec(tuple) = tuple {
-      RefCell::borrow(&tuple)
+      RefCell::borrow(tuple)
     } else {
       panic!("expected all elements to be vec");
     };
@@ -415,9 +420,9 @@ pub fn vec_remove(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
     if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
   let mut recv = RefCell::borrow_mut(&recv);
 
-  let index = match args.remove(0) {
-    Value::Num(index) if index.trunc() == index => index as usize,
-    _ => panic!("expected integer index"),
+  let index = match num::whole(&args.remove(0)) {
+    Some(index) if index >= 0 => index as usize,
+    _ => panic!("expected non-negative integer index"),
   };
 
   recv.remove(index)
@@ -430,5 +435,1173 @@ pub fn dict_remove(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
 
   let index = dict_key(args.remove(0));
 
-  recv.remove(&index).expect("key not found")
+  recv.shift_remove(&index).expect("key not found")
+}
+
+pub fn vec_push(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+
+  recv.borrow_mut().push(args.remove(0));
+
+  Value::Nil
+}
+
+pub fn vec_pop(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let mut recv = RefCell::borrow_mut(&recv);
+
+  recv.pop().expect("pop from empty vec")
+}
+
+pub fn vec_insert(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let mut recv = RefCell::borrow_mut(&recv);
+
+  let index = match num::whole(&args.remove(0)) {
+    Some(index) if index >= 0 => index as usize,
+    _ => panic!("expected non-negative integer index"),
+  };
+
+  if index > recv.len() {
+    panic!("insertion index out of bounds");
+  }
+
+  recv.insert(index, args.remove(0));
+
+  Value::Nil
+}
+
+pub fn vec_extend(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+
+  let other = match args.remove(0) {
+    Value::Vec(other) => RefCell::borrow(&other).clone(),
+    _ => panic!("expected arg to be vec"),
+  };
+
+  recv.borrow_mut().extend(other);
+
+  Value::Nil
+}
+
+pub fn sort(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+
+  recv.borrow_mut().sort_by(compare);
+
+  Value::Nil
+}
+
+pub fn sort_by(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+
+  let f = args.remove(0);
+
+  // The comparator may access the vec itself, so we must not hold a borrow
+  // while calling it
+  let mut vec = RefCell::borrow(&recv).clone();
+
+  vec.sort_by(|a, b| match int.call(f.clone(), vec![a.clone(), b.clone()]) {
+    Value::Int(ord) => ord.cmp(&0),
+    Value::Num(ord) => ord.partial_cmp(&0.0).expect("comparator returned NaN"),
+    _ => panic!("expected comparator to return num"),
+  });
+
+  *recv.borrow_mut() = vec;
+
+  Value::Nil
+}
+
+pub fn reverse(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+
+  recv.borrow_mut().reverse();
+
+  Value::Nil
+}
+
+pub fn reduce(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  // The callback may change the vec, so it's iterated over as a copy
+  let vec = RefCell::borrow(&recv).clone();
+
+  let f = args.remove(0);
+  let init = args.remove(0);
+
+  vec.into_iter().fold(init, |acc, val| int.call(f.clone(), vec![acc, val]))
+}
+
+pub fn vec_find(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let vec = RefCell::borrow(&recv).clone();
+
+  let f = args.remove(0);
+
+  vec
+    .into_iter()
+    .find(|val| int.call(f.clone(), vec![val.clone()]).is_truthy())
+    .unwrap_or(Value::Nil)
+}
+
+pub fn any(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let vec = RefCell::borrow(&recv).clone();
+
+  let f = args.remove(0);
+
+  Value::Bool(
+    vec.into_iter().any(|val| int.call(f.clone(), vec![val]).is_truthy()),
+  )
+}
+
+pub fn all(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let vec = RefCell::borrow(&recv).clone();
+
+  let f = args.remove(0);
+
+  Value::Bool(
+    vec.into_iter().all(|val| int.call(f.clone(), vec![val]).is_truthy()),
+  )
+}
+
+pub fn index_of(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  let target = args.remove(0);
+
+  match recv.iter().position(|val| *val == target) {
+    Some(index) => Value::Int(index as i64),
+    None => Value::Nil,
+  }
+}
+
+pub fn unique(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  // Values are neither hashable nor ordered in general, hence the quadratic
+  // lookup
+  let mut vec = Vec::new();
+
+  for val in recv.iter() {
+    if !vec.contains(val) {
+      vec.push(val.clone());
+    }
+  }
+
+  Value::Vec(Rc::new(RefCell::new(vec)))
+}
+
+pub fn flatten(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  let mut vec = Vec::new();
+
+  for val in recv.iter() {
+    match val {
+      Value::Vec(inner) => vec.extend(RefCell::borrow(inner).iter().cloned()),
+      _ => vec.push(val.clone()),
+    }
+  }
+
+  Value::Vec(Rc::new(RefCell::new(vec)))
+}
+
+pub fn zip(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  let other = match args.remove(0) {
+    Value::Vec(other) => other,
+    _ => panic!("expected arg to be vec"),
+  };
+  let other = RefCell::borrow(&other);
+
+  let vec = recv
+    .iter()
+    .zip(other.iter())
+    .map(|(l, r)| Value::Vec(Rc::new(RefCell::new(vec![l.clone(), r.clone()]))))
+    .collect();
+
+  Value::Vec(Rc::new(RefCell::new(vec)))
+}
+
+pub fn enumerate(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  let vec = recv
+    .iter()
+    .enumerate()
+    .map(|(i, val)| {
+      Value::Vec(Rc::new(RefCell::new(vec![Value::Int(i as i64), val.clone()])))
+    })
+    .collect();
+
+  Value::Vec(Rc::new(RefCell::new(vec)))
+}
+
+pub fn chunks(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  let size = match num::whole(&args.remove(0)) {
+    Some(size) if size > 0 => size as usize,
+    _ => panic!("expected positive integer chunk size"),
+  };
+
+  let vec = recv
+    .chunks(size)
+    .map(|chunk| Value::Vec(Rc::new(RefCell::new(chunk.to_vec()))))
+    .collect();
+
+  Value::Vec(Rc::new(RefCell::new(vec)))
+}
+
+pub fn vec_min(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  recv.iter().cloned().min_by(compare).unwrap_or(Value::Nil)
+}
+
+pub fn vec_max(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  recv.iter().cloned().max_by(compare).unwrap_or(Value::Nil)
+}
+
+pub fn sum(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  recv.iter().fold(Value::Int(0), |acc, val| match val {
+    Value::Int(_) | Value::Num(_) =>
+      num::arith(BinaryOp::Sum, acc, val.clone()),
+    _ => panic!("expected all elements to be nums"),
+  })
+}
+
+pub fn vec_join(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  let sep = match args.remove(0) {
+    Value::String(sep) => sep,
+    _ => panic!("expected arg to be string"),
+  };
+
+  Value::String(recv.iter().join(&sep))
+}
+
+pub fn compare(a: &Value, b: &Value) -> Ordering {
+  match (a, b) {
+    (Value::Int(a), Value::Int(b)) => a.cmp(b),
+    (Value::Int(_) | Value::Num(_), Value::Int(_) | Value::Num(_)) =>
+      to_f64(a).partial_cmp(&to_f64(b)).expect("cannot compare NaN"),
+    (Value::String(a), Value::String(b)) => a.cmp(b),
+    _ => panic!("can only compare two nums or two strings"),
+  }
+}
+
+pub fn keys(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  let vec = recv.keys().map(|k| Value::String(k.clone())).collect();
+
+  Value::Vec(Rc::new(RefCell::new(vec)))
+}
+
+pub fn values(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  let vec = recv.values().cloned().collect();
+
+  Value::Vec(Rc::new(RefCell::new(vec)))
+}
+
+pub fn dict_get(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  let key = dict_key(args.remove(0));
+  let default = args.remove(0);
+
+  recv.get(&key).cloned().unwrap_or(default)
+}
+
+pub fn set_default(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
+  let mut recv = RefCell::borrow_mut(&recv);
+
+  let key = dict_key(args.remove(0));
+  let default = args.remove(0);
+
+  recv.entry(key).or_insert(default).clone()
+}
+
+pub fn merge(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
+
+  let other = match args.remove(0) {
+    Value::Dict(other) => other,
+    _ => panic!("expected arg to be dict"),
+  };
+
+  let merged = deep_merge(&RefCell::borrow(&recv), &RefCell::borrow(&other));
+
+  Value::Dict(Rc::new(RefCell::new(merged)))
+}
+
+pub fn dict_filter(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
+  // The callback may change the dict, so it's iterated over as a copy
+  let dict = RefCell::borrow(&recv).clone();
+
+  let f = args.remove(0);
+
+  let dict = dict
+    .into_iter()
+    .filter(|(k, v)| {
+      int.call(f.clone(), vec![Value::String(k.clone()), v.clone()]).is_truthy()
+    })
+    .collect();
+
+  Value::Dict(Rc::new(RefCell::new(dict)))
+}
+
+pub fn dict_map(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
+  let dict = RefCell::borrow(&recv).clone();
+
+  let f = args.remove(0);
+
+  let dict = dict
+    .into_iter()
+    .map(|(k, v)| {
+      let v = int.call(f.clone(), vec![Value::String(k.clone()), v]);
+      (k, v)
+    })
+    .collect();
+
+  Value::Dict(Rc::new(RefCell::new(dict)))
+}
+
+pub fn pick(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  let keys = dict_keys_arg(args.remove(0));
+
+  let dict = recv
+    .iter()
+    .filter(|(k, _)| keys.contains(k))
+    .map(|(k, v)| (k.clone(), v.clone()))
+    .collect();
+
+  Value::Dict(Rc::new(RefCell::new(dict)))
+}
+
+pub fn omit(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv =
+    if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
+  let recv = RefCell::borrow(&recv);
+
+  let keys = dict_keys_arg(args.remove(0));
+
+  let dict = recv
+    .iter()
+    .filter(|(k, _)| !keys.contains(k))
+    .map(|(k, v)| (k.clone(), v.clone()))
+    .collect();
+
+  Value::Dict(Rc::new(RefCell::new(dict)))
+}
+
+pub fn round(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv = args.remove(0);
+
+  if args.len() > 1 {
+    panic!("expected at most 1 arg");
+  }
+
+  let digits = match args.pop() {
+    None => 0,
+    Some(Value::Int(digits)) => digits,
+    Some(_) => panic!("expected arg to be int"),
+  };
+
+  match recv {
+    Value::Int(_) if digits >= 0 => recv,
+    _ => {
+      let scale = 10f64.powi(digits as i32);
+      let num = (to_f64(&recv) * scale).round() / scale;
+
+      if digits > 0 { Value::Num(num) } else { float_to_int(num) }
+    }
+  }
+}
+
+pub fn floor(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  match args.remove(0) {
+    Value::Num(num) => float_to_int(num.floor()),
+    int => int,
+  }
+}
+
+pub fn ceil(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  match args.remove(0) {
+    Value::Num(num) => float_to_int(num.ceil()),
+    int => int,
+  }
+}
+
+pub fn abs(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  match args.remove(0) {
+    Value::Int(int) => match int.checked_abs() {
+      Some(int) => Value::Int(int),
+      None => Value::Num((int as f64).abs()),
+    },
+    Value::Num(num) => Value::Num(num.abs()),
+    _ => unreachable!(),
+  }
+}
+
+pub fn to_fixed(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv = args.remove(0);
+
+  let digits = match args.remove(0) {
+    Value::Int(digits) if digits >= 0 => digits as usize,
+    _ => panic!("expected arg to be non-negative int"),
+  };
+
+  Value::String(format!("{:.*}", digits, to_f64(&recv)))
+}
+
+pub fn num_to_string(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let recv = args.remove(0);
+
+  if args.len() > 1 {
+    panic!("expected at most 1 arg");
+  }
+
+  let radix = match args.pop() {
+    None => 10,
+    Some(Value::Int(radix)) if (2..=36).contains(&radix) => radix as u32,
+    Some(_) => panic!("expected radix to be int between 2 and 36"),
+  };
+
+  if radix == 10 {
+    return Value::String(recv.to_string());
+  }
+
+  let int = if let Value::Int(int) = recv {
+    int
+  } else {
+    panic!("only integers can be formatted with a radix other than 10")
+  };
+
+  // Build the digits from least to most significant
+  let mut digits = Vec::new();
+  let mut rest = (int as i128).abs();
+
+  loop {
+    let digit = (rest % radix as i128) as u32;
+    digits.push(std::char::from_digit(digit, radix).unwrap());
+    rest /= radix as i128;
+
+    if rest == 0 {
+      break;
+    }
+  }
+
+  if int < 0 {
+    digits.push('-');
+  }
+
+  Value::String(digits.iter().rev().collect())
+}
+
+pub fn is_integer(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  match args.remove(0) {
+    Value::Int(_) => Value::Bool(true),
+    Value::Num(num) => Value::Bool(num.is_finite() && num.fract() == 0.),
+    _ => unreachable!(),
+  }
+}
+
+fn deep_merge(
+  lhs: &IndexMap<String, Value>,
+  rhs: &IndexMap<String, Value>,
+) -> IndexMap<String, Value> {
+  let mut res = lhs.clone();
+
+  for (k, v) in rhs.iter() {
+    let merged = match (res.get(k), v) {
+      (Some(Value::Dict(l)), Value::Dict(r)) => Value::Dict(Rc::new(
+        RefCell::new(deep_merge(&RefCell::borrow(l), &RefCell::borrow(r))),
+      )),
+      _ => v.clone(),
+    };
+
+    res.insert(k.clone(), merged);
+  }
+
+  res
+}
+
+fn dict_keys_arg(val: Value) -> Vec<String> {
+  match val {
+    Value::Vec(vec) =>
+      RefCell::borrow(&vec).iter().cloned().map(dict_key).collect(),
+    _ => panic!("expected arg to be vec of keys"),
+  }
+}
+
+pub fn to_f64(val: &Value) -> f64 {
+  match val {
+    Value::Int(int) => *int as f64,
+    Value::Num(num) => *num,
+    _ => unreachable!(),
+  }
+}
+
+fn float_to_int(num: f64) -> Value {
+  // Whole floats are turned into integers when they fit
+  if num >= i64::MIN as f64 && num < i64::MAX as f64 {
+    Value::Int(num as i64)
+  } else {
+    Value::Num(num)
+  }
+}
+
+pub fn module(
+  funcs: &[(&str, Option<usize>, NativeFn)],
+) -> IndexMap<String, Value> {
+  funcs
+    .iter()
+    .map(|(name, params, func)| {
+      (
+        name.to_string(),
+        Value::Func(Func::Native {
+          name:     name.to_string(),
+          params:   *params,
+          func:     Rc::new(*func),
+          receiver: None,
+        }),
+      )
+    })
+    .collect()
+}
+
+pub fn fs_module() -> Value {
+  Value::Dict(Rc::new(RefCell::new(module(&[
+    ("read", Some(1), fs_read),
+    ("readBytes", Some(1), fs_read_bytes),
+    ("write", Some(2), fs_write),
+    ("append", Some(2), fs_append),
+    ("exists", Some(1), fs_exists),
+    ("isDir", Some(1), fs_is_dir),
+    ("list", Some(1), fs_list),
+    ("walk", Some(1), fs_walk),
+    ("mkdirs", Some(1), fs_mkdirs),
+    ("remove", Some(1), fs_remove),
+    ("copy", Some(2), fs_copy),
+    ("move", Some(2), fs_move),
+    ("stat", Some(1), fs_stat),
+    ("tempFile", Some(0), fs_temp_file),
+    ("tempDir", Some(0), fs_temp_dir),
+    ("isError", Some(1), fs_is_error),
+  ]))))
+}
+
+pub fn fs_read(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+
+  match fs::read_to_string(&path) {
+    Ok(content) => Value::String(content),
+    Err(err) => fs_error("read", &path, err),
+  }
+}
+
+pub fn fs_read_bytes(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+
+  match fs::read(&path) {
+    Ok(bytes) => Value::Vec(Rc::new(RefCell::new(
+      bytes.into_iter().map(|byte| Value::Int(byte as i64)).collect(),
+    ))),
+    Err(err) => fs_error("readBytes", &path, err),
+  }
+}
+
+pub fn fs_write(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+  let content = content_arg(args.remove(0));
+
+  match fs::write(&path, content) {
+    Ok(()) => Value::Nil,
+    Err(err) => fs_error("write", &path, err),
+  }
+}
+
+pub fn fs_append(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+  let content = content_arg(args.remove(0));
+
+  let res = fs::OpenOptions::new()
+    .append(true)
+    .create(true)
+    .open(&path)
+    .and_then(|mut file| file.write_all(&content));
+
+  match res {
+    Ok(()) => Value::Nil,
+    Err(err) => fs_error("append", &path, err),
+  }
+}
+
+pub fn fs_exists(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  Value::Bool(Path::new(&path_arg(args.remove(0))).exists())
+}
+
+pub fn fs_is_dir(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  Value::Bool(Path::new(&path_arg(args.remove(0))).is_dir())
+}
+
+pub fn fs_list(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+
+  let names = fs::read_dir(&path).and_then(|entries| {
+    entries
+      .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+      .collect::<io::Result<Vec<_>>>()
+  });
+
+  match names {
+    Ok(mut names) => {
+      names.sort();
+      Value::Vec(Rc::new(RefCell::new(
+        names.into_iter().map(Value::String).collect(),
+      )))
+    }
+    Err(err) => fs_error("list", &path, err),
+  }
+}
+
+pub fn fs_walk(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+
+  let mut paths = Vec::new();
+  if let Err(err) = walk_dir(Path::new(&path), &mut paths) {
+    return fs_error("walk", &path, err);
+  }
+
+  Value::Vec(Rc::new(RefCell::new(
+    paths
+      .into_iter()
+      .map(|path| Value::String(path.to_string_lossy().into_owned()))
+      .collect(),
+  )))
+}
+
+pub fn fs_mkdirs(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+
+  match fs::create_dir_all(&path) {
+    Ok(()) => Value::Nil,
+    Err(err) => fs_error("mkdirs", &path, err),
+  }
+}
+
+pub fn fs_remove(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+
+  match remove_path(Path::new(&path)) {
+    Ok(()) => Value::Nil,
+    Err(err) => fs_error("remove", &path, err),
+  }
+}
+
+pub fn fs_copy(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let from = path_arg(args.remove(0));
+  let to = path_arg(args.remove(0));
+
+  match copy_path(Path::new(&from), Path::new(&to)) {
+    Ok(()) => Value::Nil,
+    Err((path, err)) => fs_error("copy", &path.to_string_lossy(), err),
+  }
+}
+
+// The error renaming across file systems fails with, the same on Linux and
+// macOS
+const EXDEV: i32 = 18;
+
+pub fn fs_move(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let from = path_arg(args.remove(0));
+  let to = path_arg(args.remove(0));
+
+  // Renaming fails across file systems, fall back to copying and removing
+  let res = fs::rename(&from, &to).or_else(|err| {
+    if err.raw_os_error() != Some(EXDEV) {
+      return Err(at(Path::new(&from))(err));
+    }
+
+    copy_path(Path::new(&from), Path::new(&to))?;
+    remove_path(Path::new(&from)).map_err(at(Path::new(&from)))
+  });
+
+  match res {
+    Ok(()) => Value::Nil,
+    Err((path, err)) => fs_error("move", &path.to_string_lossy(), err),
+  }
+}
+
+pub fn fs_stat(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+
+  let meta = match fs::metadata(&path) {
+    Ok(meta) => meta,
+    Err(err) => return fs_error("stat", &path, err),
+  };
+
+  let mtime = meta
+    .modified()
+    .ok()
+    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+    .map_or(Value::Nil, |since| Value::Num(since.as_secs_f64()));
+
+  #[cfg(unix)]
+  let mode = {
+    use std::os::unix::fs::PermissionsExt;
+    Value::Int((meta.permissions().mode() & 0o7777) as i64)
+  };
+  #[cfg(not(unix))]
+  let mode = Value::Nil;
+
+  let mut stat = IndexMap::new();
+  stat.insert("size".to_string(), Value::Int(meta.len() as i64));
+  stat.insert("mtime".to_string(), mtime);
+  stat.insert("mode".to_string(), mode);
+  stat.insert("isDir".to_string(), Value::Bool(meta.is_dir()));
+  stat.insert("isFile".to_string(), Value::Bool(meta.is_file()));
+
+  Value::Dict(Rc::new(RefCell::new(stat)))
+}
+
+pub fn fs_temp_file(_int: &mut Interpreter, _args: Vec<Value>) -> Value {
+  let path = temp_path();
+
+  match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+    Ok(_) => Value::String(path.to_string_lossy().into_owned()),
+    Err(err) => fs_error("tempFile", &path.to_string_lossy(), err),
+  }
+}
+
+pub fn fs_temp_dir(_int: &mut Interpreter, _args: Vec<Value>) -> Value {
+  let path = temp_path();
+
+  match fs::create_dir(&path) {
+    Ok(()) => Value::String(path.to_string_lossy().into_owned()),
+    Err(err) => fs_error("tempDir", &path.to_string_lossy(), err),
+  }
+}
+
+pub fn fs_is_error(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  Value::Bool(match args.remove(0) {
+    Value::Dict(dict) => {
+      let dict = RefCell::borrow(&dict);
+      ["error", "kind", "op", "path"].iter().all(|key| dict.contains_key(*key))
+    }
+    _ => false,
+  })
+}
+
+// Failures are returned as dicts rather than stopping the script, so that
+// scripts can tell a missing file from other errors and carry on
+fn fs_error(op: &str, path: &str, err: io::Error) -> Value {
+  let kind = match err.kind() {
+    io::ErrorKind::NotFound => "notFound",
+    io::ErrorKind::PermissionDenied => "permissionDenied",
+    io::ErrorKind::AlreadyExists => "alreadyExists",
+    io::ErrorKind::InvalidData => "invalidData",
+    _ => "other",
+  };
+
+  let mut dict = IndexMap::new();
+  dict.insert(
+    "error".to_string(),
+    Value::String(format!("fs.{} failed for '{}': {}", op, path, err)),
+  );
+  dict.insert("kind".to_string(), Value::String(kind.to_string()));
+  dict.insert("op".to_string(), Value::String(op.to_string()));
+  dict.insert("path".to_string(), Value::String(path.to_string()));
+  dict.insert(
+    "code".to_string(),
+    err.raw_os_error().map_or(Value::Nil, |code| Value::Int(code as i64)),
+  );
+
+  Value::Dict(Rc::new(RefCell::new(dict)))
+}
+
+fn path_arg(val: Value) -> String {
+  match val {
+    Value::String(path) => path,
+    _ => panic!("expected path to be string"),
+  }
+}
+
+// Files can be written from strings or from vecs of bytes
+fn content_arg(val: Value) -> Vec<u8> {
+  match val {
+    Value::String(string) => string.into_bytes(),
+    Value::Vec(vec) => RefCell::borrow(&vec)
+      .iter()
+      .map(|byte| match byte {
+        Value::Int(byte @ 0..=255) => *byte as u8,
+        _ => panic!("expected bytes to be ints between 0 and 255"),
+      })
+      .collect(),
+    _ => panic!("expected content to be string or vec of bytes"),
+  }
+}
+
+fn walk_dir(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+  let mut entries = fs::read_dir(dir)?
+    .map(|entry| Ok(entry?.path()))
+    .collect::<io::Result<Vec<_>>>()?;
+  entries.sort();
+
+  for path in entries {
+    paths.push(path.clone());
+
+    // Links to directories aren't followed, as they could lead to a cycle
+    if fs::symlink_metadata(&path)?.is_dir() {
+      walk_dir(&path, paths)?;
+    }
+  }
+
+  Ok(())
+}
+
+// Copies a file, or a directory along with everything in it. Errors come with
+// the path that couldn't be read or written
+fn copy_path(from: &Path, to: &Path) -> Result<(), (PathBuf, io::Error)> {
+  // Copying a directory into itself would go on until paths got too long
+  let from_real = fs::canonicalize(from).map_err(at(from))?;
+  if real_path(to).map_err(at(to))?.starts_with(from_real) {
+    return Err((
+      to.to_path_buf(),
+      io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "can't copy a path into itself",
+      ),
+    ));
+  }
+
+  copy_entry(from, to)
+}
+
+fn copy_entry(from: &Path, to: &Path) -> Result<(), (PathBuf, io::Error)> {
+  let meta = fs::symlink_metadata(from).map_err(at(from))?;
+
+  // Links are copied as links, as following them could lead to a cycle
+  #[cfg(unix)]
+  if meta.file_type().is_symlink() {
+    let target = fs::read_link(from).map_err(at(from))?;
+    return std::os::unix::fs::symlink(target, to).map_err(at(to));
+  }
+
+  if !meta.is_dir() {
+    let mut reader = fs::File::open(from).map_err(at(from))?;
+    let mut writer = fs::File::create(to).map_err(at(to))?;
+    io::copy(&mut reader, &mut writer).map_err(at(to))?;
+    return fs::set_permissions(to, meta.permissions()).map_err(at(to));
+  }
+
+  fs::create_dir_all(to).map_err(at(to))?;
+
+  for entry in fs::read_dir(from).map_err(at(from))? {
+    let entry = entry.map_err(at(from))?;
+    copy_entry(&entry.path(), &to.join(entry.file_name()))?;
+  }
+
+  Ok(())
+}
+
+// Tags an error with the path it's about
+fn at(path: &Path) -> impl FnOnce(io::Error) -> (PathBuf, io::Error) {
+  let path = path.to_path_buf();
+  move |err| (path, err)
+}
+
+// The canonical form of a path that may not exist yet, made from the one of
+// its closest existing ancestor
+fn real_path(path: &Path) -> io::Result<PathBuf> {
+  let path = std::env::current_dir()?.join(path);
+
+  for ancestor in path.ancestors() {
+    if let Ok(real) = fs::canonicalize(ancestor) {
+      return Ok(real.join(path.strip_prefix(ancestor).unwrap()));
+    }
+  }
+
+  Ok(path)
+}
+
+fn remove_path(path: &Path) -> io::Result<()> {
+  if fs::symlink_metadata(path)?.is_dir() {
+    fs::remove_dir_all(path)
+  } else {
+    fs::remove_file(path)
+  }
+}
+
+fn temp_path() -> PathBuf {
+  static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+  let nanos = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |since| since.subsec_nanos());
+
+  std::env::temp_dir().join(format!(
+    "koi-{}-{}-{}",
+    process::id(),
+    nanos,
+    COUNTER.fetch_add(1, AtomicOrdering::Relaxed)
+  ))
+}
+
+pub fn path_module() -> Value {
+  Value::Dict(Rc::new(RefCell::new(module(&[
+    ("join", None, path_join),
+    ("dirname", Some(1), path_dirname),
+    ("basename", Some(1), path_basename),
+    ("stem", Some(1), path_stem),
+    ("extension", Some(1), path_extension),
+    ("withExtension", Some(2), path_with_extension),
+    ("absolute", Some(1), path_absolute),
+    ("relative", Some(2), path_relative),
+    ("normalize", Some(1), path_normalize),
+    ("expandUser", Some(1), path_expand_user),
+    ("isAbsolute", Some(1), path_is_absolute),
+  ]))))
+}
+
+pub fn path_join(_int: &mut Interpreter, args: Vec<Value>) -> Value {
+  let path: PathBuf = args.into_iter().map(path_arg).collect();
+  path_value(&path)
+}
+
+pub fn path_dirname(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+
+  match Path::new(&path).parent() {
+    Some(parent) if parent.as_os_str().is_empty() => Value::String(".".into()),
+    Some(parent) => path_value(parent),
+    None => Value::String(path),
+  }
+}
+
+pub fn path_basename(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+  os_str_value(Path::new(&path).file_name())
+}
+
+pub fn path_stem(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+  os_str_value(Path::new(&path).file_stem())
+}
+
+pub fn path_extension(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+  os_str_value(Path::new(&path).extension())
+}
+
+pub fn path_with_extension(
+  _int: &mut Interpreter,
+  mut args: Vec<Value>,
+) -> Value {
+  let path = path_arg(args.remove(0));
+  let ext = path_arg(args.remove(0));
+
+  path_value(&Path::new(&path).with_extension(ext.trim_start_matches('.')))
+}
+
+pub fn path_absolute(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+  path_value(&absolute(Path::new(&path)))
+}
+
+pub fn path_relative(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = absolute(Path::new(&path_arg(args.remove(0))));
+  let base = absolute(Path::new(&path_arg(args.remove(0))));
+
+  let common = path
+    .components()
+    .zip(base.components())
+    .take_while(|(a, b)| a == b)
+    .count();
+
+  let rel: PathBuf = base
+    .components()
+    .skip(common)
+    .map(|_| Component::ParentDir)
+    .chain(path.components().skip(common))
+    .collect();
+
+  if rel.as_os_str().is_empty() {
+    Value::String(".".into())
+  } else {
+    path_value(&rel)
+  }
+}
+
+pub fn path_normalize(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+  let norm = normalize(Path::new(&path));
+
+  if norm.as_os_str().is_empty() {
+    Value::String(".".into())
+  } else {
+    path_value(&norm)
+  }
+}
+
+pub fn path_expand_user(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let path = path_arg(args.remove(0));
+
+  let rest = match path.strip_prefix('~') {
+    Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+    _ => return Value::String(path),
+  };
+
+  let home = dirs::home_dir().expect("bad home dir");
+  Value::String(format!("{}{}", home.display(), rest))
+}
+
+pub fn path_is_absolute(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  Value::Bool(Path::new(&path_arg(args.remove(0))).is_absolute())
+}
+
+fn path_value(path: &Path) -> Value {
+  Value::String(path.to_string_lossy().into_owned())
+}
+
+fn os_str_value(os_str: Option<&OsStr>) -> Value {
+  Value::String(os_str.map_or(String::new(), |s| s.to_string_lossy().into()))
+}
+
+fn absolute(path: &Path) -> PathBuf {
+  let cwd = std::env::current_dir().expect("couldn't get working dir");
+  normalize(&cwd.join(path))
+}
+
+// Resolves `.` and `..` without touching the file system, so symlinks are not
+// followed and paths don't need to exist
+fn normalize(path: &Path) -> PathBuf {
+  let mut norm = PathBuf::new();
+
+  for comp in path.components() {
+    match comp {
+      Component::CurDir => (),
+      Component::ParentDir => match norm.components().next_back() {
+        Some(Component::Normal(_)) => {
+          norm.pop();
+        }
+        Some(Component::RootDir) | Some(Component::Prefix(_)) => (),
+        _ => norm.push(".."),
+      },
+      comp => norm.push(comp),
+    }
+  }
+
+  norm
+}
+
+pub fn env_module() -> Value {
+  Value::Dict(Rc::new(RefCell::new(module(&[
+    ("get", Some(1), env_get),
+    ("set", Some(2), env_set),
+    ("unset", Some(1), env_unset),
+    ("all", Some(0), env_all),
+  ]))))
+}
+
+pub fn env_get(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let name = env_name_arg(args.remove(0));
+
+  int.cmd_env().resolve().shift_remove(&name).map_or(Value::Nil, Value::String)
+}
+
+pub fn env_set(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let name = env_name_arg(args.remove(0));
+  let val = match args.remove(0) {
+    Value::Nil => panic!("expected value to not be nil, use env.unset instead"),
+    val => Value::String(val.to_string()),
+  };
+
+  int.get_env_mut().export(&name, val);
+
+  Value::Nil
+}
+
+pub fn env_unset(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+  let name = env_name_arg(args.remove(0));
+  int.get_env_mut().export(&name, Value::Nil);
+
+  Value::Nil
+}
+
+pub fn env_all(int: &mut Interpreter, _args: Vec<Value>) -> Value {
+  Value::Dict(Rc::new(RefCell::new(
+    int
+      .cmd_env()
+      .resolve()
+      .into_iter()
+      .map(|(k, v)| (k, Value::String(v)))
+      .collect(),
+  )))
+}
+
+fn env_name_arg(val: Value) -> String {
+  match val {
+    Value::String(name) if !name.is_empty() && !name.contains('=') => name,
+    _ => panic!("expected variable name to be non-empty string without '='"),
+  }
 }
@@ -0,0 +1,117 @@
+use {super::value::Value, crate::ast::BinaryOp, std::convert::TryFrom};
+
+pub fn arith(op: BinaryOp, lhs: Value, rhs: Value) -> Value {
+  match (lhs, rhs) {
+    (Value::Int(lhs), Value::Int(rhs)) => int_arith(op, lhs, rhs),
+    (Value::Int(lhs), Value::Num(rhs)) => float_arith(op, lhs as f64, rhs),
+    (Value::Num(lhs), Value::Int(rhs)) => float_arith(op, lhs, rhs as f64),
+    (Value::Num(lhs), Value::Num(rhs)) => float_arith(op, lhs, rhs),
+    _ => panic!("invalid operands types for op {:?}", op),
+  }
+}
+
+pub fn neg(val: Value) -> Value {
+  match val {
+    Value::Int(int) => match int.checked_neg() {
+      Some(int) => Value::Int(int),
+      None => Value::Num(-(int as f64)),
+    },
+    Value::Num(num) => Value::Num(-num),
+    _ => panic!("invalid operand type for op Neg"),
+  }
+}
+
+fn int_arith(op: BinaryOp, lhs: i64, rhs: i64) -> Value {
+  let res = match op {
+    BinaryOp::Sum => lhs.checked_add(rhs),
+    BinaryOp::Sub => lhs.checked_sub(rhs),
+    BinaryOp::Mul => lhs.checked_mul(rhs),
+    // Division stays in the integers only if it's exact
+    BinaryOp::Div if rhs != 0 && lhs % rhs == 0 => lhs.checked_div(rhs),
+    BinaryOp::Div => None,
+    BinaryOp::IntDiv => {
+      if rhs == 0 {
+        panic!("integer division by zero");
+      }
+
+      lhs.checked_div(rhs).map(|quot| {
+        // Round towards negative infinity
+        if lhs % rhs != 0 && (lhs < 0) != (rhs < 0) { quot - 1 } else { quot }
+      })
+    }
+    BinaryOp::Mod => {
+      if rhs == 0 {
+        panic!("integer modulo by zero");
+      }
+
+      // Takes the sign of the divisor, to agree with `//`
+      lhs.checked_rem(rhs).map(|rem| {
+        if rem != 0 && (rem < 0) != (rhs < 0) { rem + rhs } else { rem }
+      })
+    }
+    BinaryOp::Pow =>
+      u32::try_from(rhs).ok().and_then(|rhs| lhs.checked_pow(rhs)),
+
+    BinaryOp::BitAnd => Some(lhs & rhs),
+    BinaryOp::BitOr => Some(lhs | rhs),
+    BinaryOp::Shl | BinaryOp::Shr => {
+      let rhs = u32::try_from(rhs).expect("shift amount must be non-negative");
+
+      if op == BinaryOp::Shr {
+        // Shifting all the bits out leaves only the sign
+        Some(lhs.checked_shr(rhs).unwrap_or(if lhs < 0 { -1 } else { 0 }))
+      } else {
+        // Shifting bits out overflows like multiplying by a power of 2 would
+        match 2i64.checked_pow(rhs).and_then(|pow| lhs.checked_mul(pow)) {
+          Some(int) => Some(int),
+          None => return Value::Num(lhs as f64 * 2f64.powf(rhs as f64)),
+        }
+      }
+    }
+
+    BinaryOp::Less => return Value::Bool(lhs < rhs),
+    BinaryOp::Great => return Value::Bool(lhs > rhs),
+    _ => unreachable!(),
+  };
+
+  // Results that don't fit in an integer are promoted to floats
+  match res {
+    Some(int) => Value::Int(int),
+    None => float_arith(op, lhs as f64, rhs as f64),
+  }
+}
+
+fn float_arith(op: BinaryOp, lhs: f64, rhs: f64) -> Value {
+  match op {
+    BinaryOp::Sum => Value::Num(lhs + rhs),
+    BinaryOp::Sub => Value::Num(lhs - rhs),
+    BinaryOp::Mul => Value::Num(lhs * rhs),
+    BinaryOp::Div => Value::Num(lhs / rhs),
+    BinaryOp::IntDiv => Value::Num((lhs / rhs).floor()),
+    BinaryOp::Mod => {
+      let rem = lhs % rhs;
+      Value::Num(if rem != 0.0 && (rem < 0.0) != (rhs < 0.0) {
+        rem + rhs
+      } else {
+        rem
+      })
+    }
+    BinaryOp::Pow => Value::Num(lhs.powf(rhs)),
+    BinaryOp::Less => Value::Bool(lhs < rhs),
+    BinaryOp::Great => Value::Bool(lhs > rhs),
+    BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::Shl | BinaryOp::Shr =>
+      panic!("expected integer operands for op {:?}", op),
+    _ => unreachable!(),
+  }
+}
+
+// The integer a number holds, if it's whole. Used where the language expects
+// integers, so that nums like `4.0` are accepted too
+pub fn whole(val: &Value) -> Option<i64> {
+  match *val {
+    Value::Int(int) => Some(int),
+    Value::Num(num) if num.trunc() == num && num.abs() < i64::MAX as f64 =>
+      Some(num as i64),
+    _ => None,
+  }
+}
@@ -0,0 +1,131 @@
+use {
+  super::{env::Var, value::Value, Interpreter},
+  crate::ast::Prog,
+  indexmap::IndexMap,
+  serde_json::{Map as JSONMap, Number as JSONNumber, Value as JSONValue},
+  std::{path::PathBuf, sync::Arc},
+};
+
+// The variables of a script that has been run, in a form that can be sent to
+// other threads. Data is copied over, while functions and ranges are set up
+// again by running the declarations and `let`s they come from
+pub struct Snapshot {
+  vars:    Vec<SavedVar>,
+  modules: IndexMap<PathBuf, Vec<SavedVar>>,
+}
+
+// Values that can't be copied, like closures, are `None`
+struct SavedVar {
+  name:   String,
+  val:    Option<JSONValue>,
+  typ:    &'static str,
+  is_exp: bool,
+}
+
+impl Interpreter {
+  pub(crate) fn snapshot(&self) -> Snapshot {
+    let mut vars = self.builtins.borrow().defined(&self.builtins);
+    vars.extend(self.get_env().defined(&self.builtins));
+
+    Snapshot {
+      vars:    save_vars(vars),
+      modules: self
+        .modules
+        .iter()
+        .map(|(path, module)| (path.clone(), save_vars(module.declared())))
+        .collect(),
+    }
+  }
+
+  // Sets up the state of the script the snapshot was taken of, by running only
+  // its function declarations, imports and the `let`s of values that couldn't
+  // be copied, with the saved variables in scope
+  pub(crate) fn restore_snapshot(
+    &mut self,
+    prog: Prog,
+    snapshot: Arc<Snapshot>,
+  ) {
+    self.define_saved(&snapshot.vars);
+
+    self.restoring = Some(Arc::clone(&snapshot));
+    self.run(prog);
+    self.restoring = None;
+
+    // Values set somewhere other than their `let` can't be set up again
+    for var in snapshot.vars.iter().filter(|var| var.val.is_none()) {
+      if !self.has_var(&var.name)
+        || self.get_var(&var.name).type_name() != var.typ
+      {
+        panic!(
+          "can't copy '{}' to other threads, as it's not set by its let",
+          var.name
+        );
+      }
+    }
+  }
+
+  // Defines the saved variables of a module that's being imported again
+  pub(super) fn restore_module_vars(&mut self, path: &PathBuf) {
+    let snapshot = match &self.restoring {
+      Some(snapshot) => Arc::clone(snapshot),
+      None => return,
+    };
+
+    if let Some(vars) = snapshot.modules.get(path) {
+      self.define_saved(vars);
+    }
+  }
+
+  // Whether the `let` of a variable has to be run again to set it up
+  pub(super) fn is_uncopied(&self, name: &str) -> bool {
+    match &self.restoring {
+      Some(snapshot) => snapshot
+        .vars
+        .iter()
+        .chain(snapshot.modules.values().flatten())
+        .any(|var| var.name == name && var.val.is_none()),
+      None => false,
+    }
+  }
+
+  fn define_saved(&mut self, vars: &[SavedVar]) {
+    for var in vars {
+      if let Some(val) = &var.val {
+        let val = Value::from(val.clone());
+        self.get_env_mut().def(var.name.clone(), Var::new(val, var.is_exp));
+      }
+    }
+  }
+}
+
+fn save_vars(vars: IndexMap<String, Var>) -> Vec<SavedVar> {
+  vars
+    .into_iter()
+    .map(|(name, var)| SavedVar {
+      name,
+      val: to_json(&var.val),
+      typ: var.val.type_name(),
+      is_exp: var.is_exp,
+    })
+    .collect()
+}
+
+fn to_json(val: &Value) -> Option<JSONValue> {
+  Some(match val {
+    Value::Nil => JSONValue::Null,
+    Value::Int(int) => JSONValue::Number(JSONNumber::from(*int)),
+    Value::Num(num) => JSONValue::Number(JSONNumber::from_f64(*num)?),
+    Value::String(str) => JSONValue::String(str.clone()),
+    Value::Bool(bool) => JSONValue::Bool(*bool),
+    Value::Vec(vec) =>
+      JSONValue::Array(vec.borrow().iter().map(to_json).collect::<Option<_>>()?),
+    Value::Dict(dict) => JSONValue::Object(
+      dict
+        .borrow()
+        .iter()
+        .map(|(k, v)| Some((k.clone(), to_json(v)?)))
+        .collect::<Option<JSONMap<_, _>>>()?,
+    ),
+    Value::Func(_) | Value::Range(..) => return None,
+  })
+}
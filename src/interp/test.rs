@@ -31,10 +31,10 @@ fn vec_equality() {
 
 #[test]
 fn dict_equality() {
-  let mut a = HashMap::new();
+  let mut a = IndexMap::new();
   a.insert("foo".to_owned(), Value::Num(1.0));
 
-  let mut b = HashMap::new();
+  let mut b = IndexMap::new();
   b.insert("foo".to_owned(), Value::Num(1.0));
 
   assert_eq!(
@@ -58,16 +58,13 @@ fn to_string_vec() {
 
 #[test]
 fn to_string_dict() {
-  let mut dict = Value::Dict(Rc::new(RefCell::new(HashMap::new())));
+  let mut dict = Value::Dict(Rc::new(RefCell::new(IndexMap::new())));
   assert_eq!(dict.to_string(), "{}");
   if let Value::Dict(dict) = &mut dict {
     dict.borrow_mut().insert("foo".to_owned(), Value::Bool(true));
     dict.borrow_mut().insert("bar".to_owned(), Value::String("baz".to_owned()));
   }
-  assert!(
-    ["{foo: true, bar: 'baz'}", "{bar: 'baz', foo: true}"]
-      .contains(&&dict.to_string()[..])
-  );
+  assert_eq!(dict.to_string(), "{foo: true, bar: 'baz'}");
 }
 
 #[test]
@@ -142,6 +139,109 @@ fn arithmetic() {
   assert_eq!(output("print(2^4)", None), "16\n".to_string());
 }
 
+#[test]
+fn integer_arithmetic() {
+  assert_eq!(
+    output("print(7 / 2, 7 // 2, -7 // 2, 7.5 // 2, -7 % 3)", None),
+    "3.5 3 -4 3 2\n".to_string()
+  );
+  assert_eq!(
+    output("print(7 % -3, -7.5 % 2, 7.5 % -2)", None),
+    "-2 0.5 -0.5\n".to_string()
+  );
+  assert_eq!(
+    output("print((8 / 2).type(), (7 / 2).type(), 2 == 2.0)", None),
+    "int num true\n".to_string()
+  );
+}
+
+#[test]
+fn native_number_rounding() {
+  assert_eq!(
+    output(
+      "print(3.14159.round(2), 2.5.round(), (-2.5).floor(), 2.1.ceil(), \
+       1234.round(-2))",
+      None
+    ),
+    "3.14 3 -3 3 1200\n".to_string()
+  );
+  assert_eq!(
+    output("print((-3).abs(), (-1.5).abs(), 2.7.floor().type())", None),
+    "3 1.5 int\n".to_string()
+  );
+}
+
+#[test]
+fn native_number_formatting() {
+  assert_eq!(
+    output(
+      "print(3.14159.toFixed(3), 255.toString(16), (-5).toString(2), \
+       1.5.toString())",
+      None
+    ),
+    "3.142 ff -101 1.5\n".to_string()
+  );
+  assert_eq!(
+    output("print(1e21, 1e-7, 1e20, 2.0.isInteger(), 2.5.isInteger())", None),
+    "1e21 1e-7 100000000000000000000 true false\n".to_string()
+  );
+}
+
+#[test]
+#[should_panic(expected = "only integers can be formatted with a radix")]
+fn native_to_string_radix_on_float() { output("print(1.5.toString(2))", None); }
+
+#[test]
+fn integer_overflow_promotes_to_float() {
+  assert_eq!(
+    output("let x = 9223372036854775807 print((x + 1).type(), x.type())", None),
+    "num int\n".to_string()
+  );
+  assert_eq!(output("print((2 ^ 70).type())", None), "num\n".to_string());
+}
+
+#[test]
+fn bitwise_operators() {
+  assert_eq!(
+    output("print(0xf0 | 0x0f, 0b1100 & 0b1010, 1 << 10, 0o100 >> 3)", None),
+    "255 8 1024 8\n".to_string()
+  );
+}
+
+#[test]
+fn shift_overflow_promotes_to_float() {
+  assert_eq!(
+    output(
+      "print((1 << 62).type(), (1 << 63).type(), 1 << 64, -8 >> 70)",
+      None
+    ),
+    "int num 18446744073709552000 -1\n".to_string()
+  );
+}
+
+#[test]
+fn whole_nums_as_indices() {
+  assert_eq!(
+    output(
+      "let v = [1, 2, 3] v[1.0] = 5 print(v[math.sqrt(4)], v[0.0..2.0])",
+      None
+    ),
+    "3 [1, 5]\n".to_string()
+  );
+}
+
+#[test]
+#[should_panic(expected = "expected non-negative integer index")]
+fn fractional_index() { output("print([1, 2][0.5])", None); }
+
+#[test]
+#[should_panic(expected = "expected integer operands")]
+fn bitwise_operators_on_floats() { output("print(1.5 | 1)", None); }
+
+#[test]
+#[should_panic(expected = "integer division by zero")]
+fn integer_division_by_zero() { output("print(1 // 0)", None); }
+
 #[test]
 fn arithmetic_assignment() {
   assert_eq!(output("let x = 17 x += 3 print(x)", None), "20\n".to_string());
@@ -205,6 +305,130 @@ fn exported_var() {
   );
 }
 
+#[test]
+fn modified_imported_var_is_reexported() {
+  std::env::set_var("IMPORTED_TEST_VAR", "os");
+
+  assert_eq!(
+    output(
+      "print(IMPORTED_TEST_VAR)\nIMPORTED_TEST_VAR = 'koi'\nenv | grep \
+       '^IMPORTED_TEST_VAR='",
+      None
+    ),
+    "os\nIMPORTED_TEST_VAR=koi\n".to_string()
+  );
+}
+
+#[test]
+fn native_env() {
+  let source = "
+    env.set('KOI_TEST_VAR', 42)
+    print(env.get('KOI_TEST_VAR'), KOI_TEST_VAR, env.all().KOI_TEST_VAR)
+    env | grep KOI_TEST_VAR
+    env.unset('KOI_TEST_VAR')
+    print(env.get('KOI_TEST_VAR'), env.all().contains('KOI_TEST_VAR'))
+    env | grep -c KOI_TEST_VAR
+  ";
+
+  assert_eq!(
+    output(source, None),
+    "42 42 42\nKOI_TEST_VAR=42\nnil false\n0\n".to_string()
+  );
+}
+
+#[test]
+fn native_env_unset_imported() {
+  assert_eq!(
+    output("env.unset('USER')\nenv | grep -c '^USER='", None),
+    "0\n".to_string()
+  );
+}
+
+#[test]
+fn native_env_get_ignores_unexported() {
+  assert_eq!(
+    output("let KOI_LOCAL = 1 print(env.get('KOI_LOCAL'))", None),
+    "nil\n".to_string()
+  );
+}
+
+#[test]
+fn cmd_env_prefix() {
+  let source = "
+    let x = 'a b'
+    KOI_A=1 KOI_B={x} env | grep '^KOI_' | sort
+    env | grep -c '^KOI_'
+    print($(KOI_A='{x}!' printenv KOI_A))
+  ";
+
+  assert_eq!(
+    output(source, None),
+    "KOI_A=1\nKOI_B=a b\n0\na b!\n\n".to_string()
+  );
+}
+
+#[test]
+fn with_env_block() {
+  let source = "
+    exp let KOI_A = 'outer'
+    withEnv({KOI_A: 'inner', KOI_B: 1}) {
+      env | grep '^KOI_' | sort
+      withEnv({KOI_B: nil}) {
+        env | grep '^KOI_'
+      }
+    }
+    env | grep '^KOI_'
+  ";
+
+  assert_eq!(
+    output(source, None),
+    "KOI_A=inner\nKOI_B=1\nKOI_A=inner\nKOI_A=outer\n".to_string()
+  );
+}
+
+#[test]
+fn with_env_clear() {
+  let source = "
+    exp let KOI_A = 'a'
+    withEnv({KOI_B: 'b'}, {clear: true}) {
+      env
+    }
+    withEnv({}, {keep: ['KOI_A', 'KOI_MISSING']}) {
+      env
+      KOI_C=c env | sort
+    }
+  ";
+
+  assert_eq!(
+    output(source, None),
+    "KOI_B=b\nKOI_A=a\nKOI_A=a\nKOI_C=c\n".to_string()
+  );
+}
+
+#[test]
+fn with_env_clear_keeps_exports_from_block() {
+  let source = "
+    exp let KOI_A = 'a'
+    withEnv({KOI_B: 'b'}, {clear: true}) {
+      exp let KOI_C = 'c'
+      env.set('KOI_A', 'changed')
+      env | sort
+    }
+    print(KOI_A)
+  ";
+
+  assert_eq!(
+    output(source, None),
+    "KOI_A=changed\nKOI_B=b\nKOI_C=c\nchanged\n".to_string()
+  );
+}
+
+#[test]
+#[should_panic(expected = "unknown withEnv option 'clean'")]
+fn with_env_unknown_option() {
+  output("withEnv({}, {clean: true}) {\n  env\n}", None);
+}
+
 #[test]
 fn imported_var() {
   assert_eq!(
@@ -416,6 +640,17 @@ fn native_map() {
   );
 }
 
+#[test]
+fn commented_lambda_arg() {
+  assert_eq!(
+    output(
+      "print([1, 2].map(\n  # doubles\n  fn (x) {\n    return x * 2\n  }\n))",
+      None
+    ),
+    "[2, 4]\n".to_string()
+  );
+}
+
 #[test]
 fn native_filter() {
   assert_eq!(
@@ -558,3 +793,709 @@ fn native_bool() {
   assert_eq!(output("print(false.bool())", None), "false\n".to_string());
   assert_eq!(output("print(123.bool())", None), "true\n".to_string());
 }
+
+#[test]
+fn native_vec_push_pop() {
+  assert_eq!(
+    output("let x = [1] x.push(2) print(x) print(x.pop()) print(x)", None),
+    "[1, 2]\n2\n[1]\n".to_string()
+  );
+}
+
+#[test]
+fn native_vec_insert() {
+  assert_eq!(
+    output("let x = [1 3] x.insert(1, 2) print(x)", None),
+    "[1, 2, 3]\n".to_string()
+  );
+}
+
+#[test]
+fn native_vec_extend() {
+  assert_eq!(
+    output("let x = [1] x.extend([2 3]) print(x)", None),
+    "[1, 2, 3]\n".to_string()
+  );
+}
+
+#[test]
+fn native_sort() {
+  assert_eq!(
+    output("let x = [3 1 2] x.sort() print(x)", None),
+    "[1, 2, 3]\n".to_string()
+  );
+  assert_eq!(
+    output("let x = ['b' 'c' 'a'] x.sort() print(x)", None),
+    "['a', 'b', 'c']\n".to_string()
+  );
+}
+
+#[test]
+#[should_panic]
+fn native_sort_mixed_types() { output("[1 'a'].sort()", None); }
+
+#[test]
+fn native_sort_by() {
+  assert_eq!(
+    output("let x = [3 1 2] x.sortBy(fn(a, b) {return b - a}) print(x)", None),
+    "[3, 2, 1]\n".to_string()
+  );
+}
+
+#[test]
+fn native_reverse() {
+  assert_eq!(
+    output("let x = [1 2 3] x.reverse() print(x)", None),
+    "[3, 2, 1]\n".to_string()
+  );
+}
+
+#[test]
+fn native_reduce() {
+  assert_eq!(
+    output("print([1 2 3].reduce(fn(acc, i) {return acc + i}, 10))", None),
+    "16\n".to_string()
+  );
+}
+
+#[test]
+fn native_vec_find() {
+  assert_eq!(
+    output("print([1 2 3 4].find(fn(i) {return i > 2}))", None),
+    "3\n".to_string()
+  );
+  assert_eq!(
+    output("print([1 2].find(fn(i) {return i > 2}))", None),
+    "nil\n".to_string()
+  );
+}
+
+#[test]
+fn native_any_all() {
+  assert_eq!(
+    output(
+      "let even = fn(i) {return i % 2 == 0} print([1 2].any(even), [1 \
+       2].all(even))",
+      None
+    ),
+    "true false\n".to_string()
+  );
+}
+
+#[test]
+fn native_callbacks_can_change_vec() {
+  assert_eq!(
+    output(
+      "let x = [1 2]
+       let push = fn(i) {
+         x.push(i)
+         return false
+       }
+       print(x.any(push), x.find(push), x.all(push), x)
+       print(x.reduce(fn(acc, i) {
+         x.pop()
+         return acc + i
+       }, 0), x)",
+      None
+    ),
+    "false nil false [1, 2, 1, 2, 1, 2, 1, 2, 1]\n13 []\n".to_string()
+  );
+}
+
+#[test]
+fn native_index_of() {
+  assert_eq!(
+    output("print(['a' 'b'].indexOf('b'), ['a'].indexOf('c'))", None),
+    "1 nil\n".to_string()
+  );
+}
+
+#[test]
+fn native_unique() {
+  assert_eq!(
+    output("print([1 2 1 3 2].unique())", None),
+    "[1, 2, 3]\n".to_string()
+  );
+}
+
+#[test]
+fn native_flatten() {
+  assert_eq!(
+    output("print([[1 2], 3, [4]].flatten())", None),
+    "[1, 2, 3, 4]\n".to_string()
+  );
+}
+
+#[test]
+fn native_zip() {
+  assert_eq!(
+    output("print([1 2 3].zip(['a' 'b']))", None),
+    "[[1, 'a'], [2, 'b']]\n".to_string()
+  );
+}
+
+#[test]
+fn native_enumerate() {
+  assert_eq!(
+    output("print(['a' 'b'].enumerate())", None),
+    "[[0, 'a'], [1, 'b']]\n".to_string()
+  );
+}
+
+#[test]
+fn native_chunks() {
+  assert_eq!(
+    output("print([1 2 3 4 5].chunks(2))", None),
+    "[[1, 2], [3, 4], [5]]\n".to_string()
+  );
+}
+
+#[test]
+fn native_vec_min_max() {
+  assert_eq!(
+    output("print([3 1 2].min(), [3 1 2].max(), [].min())", None),
+    "1 3 nil\n".to_string()
+  );
+}
+
+#[test]
+fn native_sum() {
+  assert_eq!(output("print([1 2 3].sum())", None), "6\n".to_string());
+}
+
+#[test]
+#[should_panic]
+fn native_sum_non_num() { output("[1 'a'].sum()", None); }
+
+#[test]
+fn native_vec_join() {
+  assert_eq!(output("print([1 'a' 2].join('-'))", None), "1-a-2\n".to_string());
+}
+
+#[test]
+fn native_keys_values() {
+  assert_eq!(
+    output("print({a: 1}.keys(), {a: 1}.values())", None),
+    "['a'] [1]\n".to_string()
+  );
+}
+
+#[test]
+fn native_dict_get() {
+  assert_eq!(
+    output("let x = {a: 1} print(x.get('a', 0), x.get('b', 0))", None),
+    "1 0\n".to_string()
+  );
+}
+
+#[test]
+fn native_set_default() {
+  assert_eq!(
+    output(
+      "let x = {a: 1} print(x.setDefault('a', 2), x.setDefault('b', 3)) \
+       print(x.b)",
+      None
+    ),
+    "1 3\n3\n".to_string()
+  );
+}
+
+#[test]
+fn native_merge() {
+  assert_eq!(
+    output(
+      "let x = {a: {b: 1, c: 2}}.merge({a: {c: 3}}) print(x.a.b, x.a.c)",
+      None
+    ),
+    "1 3\n".to_string()
+  );
+}
+
+#[test]
+fn native_dict_filter() {
+  assert_eq!(
+    output("print({a: 1, b: 2}.filter(fn(k, v) {return v > 1}))", None),
+    "{b: 2}\n".to_string()
+  );
+}
+
+#[test]
+fn native_dict_map() {
+  assert_eq!(
+    output("print({a: 1}.map(fn(k, v) {return k + v.string()}))", None),
+    "{a: 'a1'}\n".to_string()
+  );
+}
+
+#[test]
+fn native_dict_callbacks_can_change_dict() {
+  assert_eq!(
+    output(
+      "let x = {a: 1} print(x.filter(fn(k, v) {x.b = 2 return true}), \
+       x.map(fn(k, v) {x.c = 3 return v}), x)",
+      None
+    ),
+    "{a: 1} {a: 1, b: 2} {a: 1, b: 2, c: 3}\n".to_string()
+  );
+}
+
+#[test]
+fn native_pick_omit() {
+  assert_eq!(
+    output(
+      "let x = {a: 1, b: 2} print(x.pick(['a', 'c']), x.omit(['a']))",
+      None
+    ),
+    "{a: 1} {b: 2}\n".to_string()
+  );
+}
+
+#[test]
+#[should_panic(expected = "dict has no key or method named 'missing'")]
+fn dict_missing_key() { output("print({a: 1}.missing)", None); }
+
+#[test]
+fn dict_insertion_order() {
+  assert_eq!(
+    output(
+      "let x = {z: 1, a: 2, m: 3} x.b = 4 x.remove('a') print(x) \
+       print(x.toJson()) for k, v in x {print(k)}",
+      None
+    ),
+    "{z: 1, m: 3, b: 4}\n{\"z\":1,\"m\":3,\"b\":4}\nz\nm\nb\n".to_string()
+  );
+  assert_eq!(
+    output("print('\\{\"z\": 1, \"a\": 2}'.parseJson().keys())", None),
+    "['z', 'a']\n".to_string()
+  );
+}
+
+#[test]
+fn native_math_functions() {
+  assert_eq!(
+    output(
+      "print(math.sqrt(16), math.log(8, 2), math.exp(0), math.cos(0), \
+       math.atan2(0, 1))",
+      None
+    ),
+    "4 3 1 1 0\n".to_string()
+  );
+  assert_eq!(
+    output(
+      "print(math.min(3, 1, 2), math.max([1, 5.5]), math.clamp(12, 0, 10), \
+       math.pi.toFixed(2))",
+      None
+    ),
+    "1 5.5 10 3.14\n".to_string()
+  );
+}
+
+#[test]
+fn native_math_seeded_random() {
+  let source = "
+    math.seed(42)
+    let a = [math.random(), math.randInt(1, 6), math.choice([1, 2, 3])]
+    math.seed(42)
+    print(a == [math.random(), math.randInt(1, 6), math.choice([1, 2, 3])])
+  ";
+
+  assert_eq!(output(source, None), "true\n".to_string());
+}
+
+#[test]
+fn native_math_random_ranges() {
+  let source = "
+    let ok = true
+    for _ in 0..200 {
+      let n = math.randInt(-2, 2)
+      let f = math.random()
+      ok = ok && n >= -2 && n <= 2 && f >= 0 && f < 1
+    }
+    let v = [1, 2, 3, 4, 5]
+    math.shuffle(v)
+    v.sort()
+    print(ok, v)
+  ";
+
+  assert_eq!(output(source, None), "true [1, 2, 3, 4, 5]\n".to_string());
+}
+
+#[test]
+fn native_fs_files() {
+  let source = "
+    let dir = fs.tempDir()
+    fs.write('{dir}/a.txt', 'hello \"quoted\" $world')
+    fs.append('{dir}/a.txt', '!')
+    fs.mkdirs('{dir}/sub/deep')
+    fs.write('{dir}/sub/deep/b.bin', [0, 255])
+    print(fs.read('{dir}/a.txt'), fs.readBytes('{dir}/sub/deep/b.bin'))
+    print(fs.list(dir), fs.walk(dir).len(), fs.stat('{dir}/a.txt').size)
+    fs.copy('{dir}/sub', '{dir}/copy')
+    fs.move('{dir}/a.txt', '{dir}/c.txt')
+    print(fs.exists('{dir}/a.txt'), fs.isDir('{dir}/copy/deep'))
+    fs.remove(dir)
+    print(fs.exists(dir))
+  ";
+
+  assert_eq!(
+    output(source, None),
+    "hello \"quoted\" $world! [0, 255]\n['a.txt', 'sub'] 4 22\nfalse \
+     true\nfalse\n"
+      .to_string()
+  );
+}
+
+#[test]
+fn native_fs_errors() {
+  let source = "
+    let err = fs.read('does/not/exist')
+    print(fs.isError(err), err.kind, err.op, err.path)
+    print(err.error)
+    print(fs.isError(fs.read('/')), fs.isError(''), fs.isError({a: 1}))
+  ";
+
+  assert_eq!(
+    output(source, None),
+    "true notFound read does/not/exist
+fs.read failed for 'does/not/exist': No such file or directory (os error \
+     2)\ntrue false false\n"
+      .to_string()
+  );
+}
+
+#[test]
+fn native_fs_symlink_cycles() {
+  let source = "
+    let dir = fs.tempDir()
+    fs.write('{dir}/a.txt', 'a')
+    ln -s {dir} {dir}/loop
+    print(fs.walk(dir).map(fn(p) {return path.basename(p)}))
+    fs.copy(dir, '{dir}.copy')
+    print(fs.list('{dir}.copy'), fs.isDir('{dir}.copy/loop'))
+    fs.remove('{dir}.copy')
+    print(fs.exists(dir), fs.exists('{dir}/a.txt'))
+    fs.remove(dir)
+  ";
+
+  assert_eq!(
+    output(source, None),
+    "['a.txt', 'loop']\n['a.txt', 'loop'] true\ntrue true\n".to_string()
+  );
+}
+
+#[test]
+fn native_fs_copy_errors() {
+  let source = "
+    let dir = fs.tempDir()
+    fs.write('{dir}/a.txt', 'a')
+    let err = fs.copy(dir, '{dir}/inner')
+    print(err.path == '{dir}/inner', err.error.contains('into itself'))
+    print(fs.list(dir))
+    err = fs.copy('{dir}/a.txt', '{dir}/no/b.txt')
+    print(err.kind, err.path == '{dir}/no/b.txt')
+    fs.remove(dir)
+  ";
+
+  assert_eq!(
+    output(source, None),
+    "true true\n['a.txt']\nnotFound true\n".to_string()
+  );
+}
+
+#[test]
+fn native_path_parts() {
+  let source = "
+    let p = path.join('/usr/', 'local', 'bin/koi.tar.gz')
+    print(p, path.dirname(p), path.basename(p), path.stem(p))
+    print(path.extension(p), path.withExtension(p, '.zip'), path.dirname('a'))
+    print(path.isAbsolute(p), path.isAbsolute('a/b'))
+  ";
+
+  assert_eq!(
+    output(source, None),
+    "/usr/local/bin/koi.tar.gz /usr/local/bin koi.tar.gz koi.tar\ngz \
+     /usr/local/bin/koi.tar.zip .\ntrue false\n"
+      .to_string()
+  );
+}
+
+#[test]
+fn native_path_normalization() {
+  let source = "
+    print(path.normalize('a/./b/../../c/'), path.normalize('../a/..'))
+    print(path.normalize('/..//x'), path.relative('/a/b/c', '/a/d'))
+    print(path.relative('x', '.'), path.expandUser('~/koi') == '{HOME}/koi')
+  ";
+
+  assert_eq!(output(source, None), "c ..\n/x ../b/c\nx true\n".to_string());
+}
+
+#[test]
+fn native_path_in_commands() {
+  assert_eq!(
+    output("let prefix = '/opt/'\necho {path.join(prefix, 'bin')}", None),
+    "/opt/bin\n".to_string()
+  );
+}
+
+#[test]
+#[should_panic(expected = "dict has no key or method named 'branch'")]
+fn module_keeps_private_members() {
+  output(
+    "import \"git\" as git\nprint(git.branch())",
+    Some(PathBuf::from("src/interp/golden")),
+  );
+}
+
+fn module_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+  let dir =
+    std::env::temp_dir().join(format!("koi-{}-{}", name, std::process::id()));
+  fs::create_dir_all(&dir).unwrap();
+
+  for (file, source) in files {
+    fs::write(dir.join(file), source).unwrap();
+  }
+
+  dir
+}
+
+#[test]
+fn runs_modules_once() {
+  let dir = module_dir("modules-once", &[
+    ("util.koi", "print('loading util')\nexp fn id(x) {\n  return x\n}"),
+    ("a.koi", "import 'util' as util\nexp let a = util.id('a')"),
+    ("b.koi", "import 'util'\nexp let b = id('b')"),
+  ]);
+
+  assert_eq!(
+    output(
+      "import 'a' as a\nimport 'b' as b\nprint(a.a + b.b)",
+      Some(dir.clone())
+    ),
+    "loading util\nab\n"
+  );
+
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "import cycle: ")]
+fn panics_on_import_cycle() {
+  let dir = module_dir("import-cycle", &[
+    ("a.koi", "import 'b'"),
+    ("b.koi", "import 'c'"),
+    ("c.koi", "import 'a'"),
+  ]);
+
+  let res =
+    std::panic::catch_unwind(|| output("import 'a'", Some(dir.clone())));
+  fs::remove_dir_all(dir).unwrap();
+
+  if let Err(err) = res {
+    std::panic::resume_unwind(err);
+  }
+}
+
+#[test]
+fn panics_on_import_cycle_through_entry_file() {
+  let dir = module_dir("entry-cycle", &[
+    ("a.koi", "print('a runs')\nimport 'b'"),
+    ("b.koi", "print('b runs')\nimport 'a'"),
+  ]);
+  let dir = fs::canonicalize(dir).unwrap();
+
+  let mut interpreter = Interpreter::new();
+  interpreter.do_collect();
+  let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+    interpreter.run_file(dir.join("a.koi"))
+  }));
+  fs::remove_dir_all(&dir).unwrap();
+
+  let (a, b) = (dir.join("a.koi"), dir.join("b.koi"));
+  assert_eq!(
+    panic_message(res.unwrap_err()),
+    format!(
+      "import cycle: {} -> {} -> {}",
+      a.display(),
+      b.display(),
+      a.display()
+    )
+  );
+  assert_eq!(interpreter.take_collected(), "a runs\nb runs\n");
+}
+
+#[test]
+fn plain_imports_run_in_current_scope() {
+  let dir = module_dir("plain-import", &[(
+    "helpers.koi",
+    "fn first() {\n  return args[0]\n}\nfn stage() {\n  return $(printenv \
+     STAGE).strip()\n}",
+  )]);
+
+  let mut interpreter = Interpreter::new();
+  interpreter.do_collect();
+  interpreter.set_import_root(dir.clone());
+  interpreter.set_args(vec!["a".to_string()]);
+  interpreter.run_source(
+    "exp let STAGE = 'prod'\nimport 'helpers'\nprint(first(), stage())",
+  );
+  fs::remove_dir_all(dir).unwrap();
+
+  assert_eq!(interpreter.take_collected(), "a prod\n");
+}
+
+#[test]
+fn imports_from_koi_path() {
+  let lib = module_dir("koi-path-lib", &[]);
+  fs::create_dir_all(lib.join("teamlib")).unwrap();
+  fs::write(lib.join("teamlib/docker.koi"), "exp let image = 'koi:latest'")
+    .unwrap();
+  let root = module_dir("koi-path-root", &[]);
+
+  std::env::set_var("KOI_PATH", &lib);
+  let out = output(
+    "import 'teamlib/docker' as docker\nprint(docker.image)",
+    Some(root.clone()),
+  );
+  std::env::remove_var("KOI_PATH");
+
+  assert_eq!(out, "koi:latest\n");
+
+  fs::remove_dir_all(lib).unwrap();
+  fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "couldn't find './teamlib/docker.koi' to import")]
+fn relative_imports_skip_search_path() {
+  output("import './teamlib/docker'", Some(std::env::temp_dir()));
+}
+
+#[test]
+#[should_panic(
+  expected = "no module named 'std/nope.koi' in the standard library"
+)]
+fn panics_on_unknown_std_module() { output("import 'std/nope'", None); }
+
+const DEPLOY_SPEC: &str = "{
+  name: 'deploy',
+  about: 'Deploys the app.',
+  flags: {
+    verbose: {short: 'v', help: 'Print more.'},
+    dry: 'Only show what would happen.',
+  },
+  options: {
+    env: {short: 'e', default: 'staging'},
+    jobs: {short: 'j', type: 'int', default: 1},
+  },
+  positionals: {target: 'What to deploy.'},
+  commands: {rollback: {positionals: {release: {type: 'int'}}}},
+}";
+
+#[test]
+fn parses_args() {
+  for (args, want) in &[
+    (
+      "['app', '-vj4', '--env=prod']",
+      "{verbose: true, dry: false, env: 'prod', jobs: 4, target: 'app', \
+       command: nil}",
+    ),
+    (
+      "['--dry', '-e', 'dev', '--', '-app', 'rollback', '3']",
+      "{verbose: false, dry: true, env: 'dev', jobs: 1, target: '-app', \
+       command: 'rollback', rollback: {release: 3}}",
+    ),
+  ] {
+    assert_eq!(
+      output(&format!("print(argparse({}, {}))", DEPLOY_SPEC, args), None),
+      format!("{}\n", want)
+    );
+  }
+}
+
+#[test]
+fn prints_help_for_args() {
+  let spec = Interpreter::new().eval_expr(
+    Parser::new(new_lexer(DEPLOY_SPEC.to_string())).parse_single_expr(),
+  );
+
+  assert_eq!(
+    argparse::Spec::new("script".to_string(), &spec).help(),
+    "deploy
+Deploys the app.
+
+USAGE:
+    deploy [FLAGS] [OPTIONS] <target> [SUBCOMMAND]
+
+FLAGS:
+    -v, --verbose    Print more.
+        --dry        Only show what would happen.
+    -h, --help       Prints help information
+
+OPTIONS:
+    -e, --env <env>      [default: staging]
+    -j, --jobs <jobs>    [default: 1]
+
+ARGS:
+    <target>    What to deploy.
+
+SUBCOMMANDS:
+    rollback
+"
+  );
+}
+
+#[test]
+#[should_panic(expected = "unexpected argument '-x'")]
+fn panics_on_unexpected_arg() {
+  output(&format!("argparse({}, ['app', '-vx'])", DEPLOY_SPEC), None);
+}
+
+#[test]
+#[should_panic(expected = "invalid value 'x' for 'release', expected int")]
+fn panics_on_invalid_arg_value() {
+  output(&format!("argparse({}, ['app', 'rollback', 'x'])", DEPLOY_SPEC), None);
+}
+
+#[test]
+#[should_panic(expected = "missing argument '<target>'")]
+fn panics_on_missing_arg() {
+  output(&format!("argparse({}, ['-v'])", DEPLOY_SPEC), None);
+}
+
+#[test]
+fn returns_help_for_args() {
+  assert_eq!(
+    output(
+      &format!(
+        "let opts = argparse({}, ['app', 'rollback', '--help'])
+print(opts.keys())
+print(opts.help.split('\n')[0])",
+        DEPLOY_SPEC
+      ),
+      None
+    ),
+    "['help']\ndeploy rollback\n"
+  );
+}
+
+#[test]
+fn parses_negative_num_args() {
+  assert_eq!(
+    output(
+      &format!("print(argparse({}, ['-5', '-j', '-2']))", DEPLOY_SPEC),
+      None
+    ),
+    "{verbose: false, dry: false, env: 'staging', jobs: -2, target: '-5', \
+     command: nil}\n"
+  );
+}
+
+#[test]
+#[should_panic(expected = "expected default of 'jobs' to be int, got string")]
+fn panics_on_invalid_arg_default() {
+  output(
+    "argparse({options: {jobs: {type: 'int', default: 'all'}}}, [])",
+    None,
+  );
+}
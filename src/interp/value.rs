@@ -1,24 +1,25 @@
 use {
   crate::interp::func::Func,
+  indexmap::IndexMap,
   itertools::Itertools,
   serde_json::{Map as JSONMap, Number as JSONNumber, Value as JSONValue},
   std::{
     cell::RefCell,
-    collections::HashMap,
     fmt::{Display, Formatter},
     rc::Rc,
   },
 };
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub enum Value {
   Nil,
+  Int(i64),
   Num(f64),
   String(String),
   Bool(bool),
 
   Vec(Rc<RefCell<Vec<Value>>>),
-  Dict(Rc<RefCell<HashMap<String, Value>>>),
+  Dict(Rc<RefCell<IndexMap<String, Value>>>),
 
   Range(usize, usize),
 
@@ -29,6 +30,13 @@ impl Display for Value {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     match self {
       Value::Nil => write!(f, "nil"),
+      Value::Int(int) => write!(f, "{}", int),
+      // Very large and very small floats use exponent notation
+      Value::Num(num)
+        if num.is_finite()
+          && *num != 0.
+          && (num.abs() >= 1e21 || num.abs() < 1e-6) =>
+        write!(f, "{:e}", num),
       Value::Num(num) => write!(f, "{}", num),
       Value::String(string) => write!(f, "{}", string),
       Value::Bool(bool) => write!(f, "{}", bool),
@@ -53,20 +61,51 @@ impl Display for Value {
   }
 }
 
+impl PartialEq for Value {
+  fn eq(&self, other: &Self) -> bool {
+    match (self, other) {
+      (Value::Nil, Value::Nil) => true,
+      (Value::Int(lhs), Value::Int(rhs)) => lhs == rhs,
+      (Value::Num(lhs), Value::Num(rhs)) => lhs == rhs,
+      // Integers and floats holding the same quantity are equal
+      (Value::Int(int), Value::Num(num))
+      | (Value::Num(num), Value::Int(int)) => *int as f64 == *num,
+      (Value::String(lhs), Value::String(rhs)) => lhs == rhs,
+      (Value::Bool(lhs), Value::Bool(rhs)) => lhs == rhs,
+      (Value::Vec(lhs), Value::Vec(rhs)) => lhs == rhs,
+      (Value::Dict(lhs), Value::Dict(rhs)) => lhs == rhs,
+      (Value::Range(l, r), Value::Range(l_other, r_other)) =>
+        l == l_other && r == r_other,
+      (Value::Func(lhs), Value::Func(rhs)) => lhs == rhs,
+      _ => false,
+    }
+  }
+}
+
 impl Value {
-  pub fn is_truthy(&self) -> bool {
+  pub fn type_name(&self) -> &'static str {
     match self {
-      Value::Nil => false,
-      Value::Bool(false) => false,
-      _ => true,
+      Value::Nil => "nil",
+      Value::Int(_) => "int",
+      Value::Num(_) => "num",
+      Value::String(_) => "string",
+      Value::Bool(_) => "bool",
+      Value::Vec(_) => "vec",
+      Value::Dict(_) => "dict",
+      Value::Range(_, _) => "range",
+      Value::Func(_) => "func",
     }
   }
 
+  pub fn is_truthy(&self) -> bool {
+    !matches!(self, Value::Nil | Value::Bool(false))
+  }
+
   pub fn to_string_quoted(&self) -> String {
     if !matches!(self, Value::String(..)) {
       self.to_string()
     } else {
-      format!("\'{}\'", self.to_string())
+      format!("\'{}\'", self)
     }
   }
 }
@@ -75,6 +114,7 @@ impl From<Value> for JSONValue {
   fn from(val: Value) -> Self {
     match val {
       Value::Nil => JSONValue::Null,
+      Value::Int(int) => JSONValue::Number(JSONNumber::from(int)),
       Value::Num(num) => JSONValue::Number(JSONNumber::from_f64(num).unwrap()),
       Value::String(str) => JSONValue::String(str),
       Value::Bool(bool) => JSONValue::Bool(bool),
@@ -108,7 +148,10 @@ impl From<JSONValue> for Value {
   fn from(json_val: JSONValue) -> Self {
     match json_val {
       JSONValue::Null => Value::Nil,
-      JSONValue::Number(json_num) => Value::Num(json_num.as_f64().unwrap()),
+      JSONValue::Number(json_num) => match json_num.as_i64() {
+        Some(int) => Value::Int(int),
+        None => Value::Num(json_num.as_f64().unwrap()),
+      },
       JSONValue::String(json_str) => Value::String(json_str),
       JSONValue::Bool(json_bool) => Value::Bool(json_bool),
       JSONValue::Array(json_vec) => {
@@ -121,7 +164,7 @@ impl From<JSONValue> for Value {
         Value::Vec(Rc::new(RefCell::new(vec)))
       }
       JSONValue::Object(json_map) => {
-        let mut map = HashMap::new();
+        let mut map = IndexMap::new();
 
         for (k, v) in json_map {
           map.insert(k, v.into());
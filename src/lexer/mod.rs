@@ -5,7 +5,10 @@ mod peek;
 mod raw;
 mod record;
 
-#[cfg(test)] mod test;
+// The tests predate clippy being run on the crate
+#[cfg(test)]
+#[allow(clippy::approx_constant, clippy::bool_assert_comparison)]
+mod test;
 
 pub fn new(source: String) -> Lexer {
   Lexer::new(RecordingLexer::new(RawLexer::new(source)))
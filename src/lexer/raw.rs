@@ -39,7 +39,7 @@ impl RawLexer {
   }
 
   fn make_lexeme(&self, from: usize, to: usize) -> String {
-    (&self.source[from..to]).iter().collect()
+    self.source[from..to].iter().collect()
   }
 
   fn scan_symbol(&mut self) -> Token {
@@ -63,11 +63,8 @@ impl RawLexer {
 
       ' ' | '\t' => {
         let mut length = 1;
-        loop {
-          match self.char_at(length) {
-            Some(' ') | Some('\t') => length += 1,
-            _ => break,
-          }
+        while let Some(' ') | Some('\t') = self.char_at(length) {
+          length += 1;
         }
         (TokenKind::Space, length)
       }
@@ -89,6 +86,9 @@ impl RawLexer {
         (TokenKind::RightBrace, 1)
       }
 
+      '/' if matches!(self.char_at(1), Some('/')) => (TokenKind::SlashSlash, 2),
+      '<' if matches!(self.char_at(1), Some('<')) => (TokenKind::LessLess, 2),
+
       // Chars that may only appear by themselves or followed by an equals sign
       '!' | '=' | '/' | '^' | '%' | '<' => {
         let (kind, equal_kind) = match self.char_at(0).unwrap() {
@@ -145,7 +145,7 @@ impl RawLexer {
           _ => (TokenKind::AmperGreat, 2),
         },
         Some('|') => (TokenKind::AmperPipe, 2),
-        _ => panic!("unexpected character"),
+        _ => (TokenKind::Amper, 1),
       },
 
       c => (TokenKind::UnknownChar(c), 1),
@@ -158,32 +158,79 @@ impl RawLexer {
     Token { lexeme, kind }
   }
 
+  fn scan_digits(&mut self, radix: u32) {
+    // Underscores are allowed as separators, but only between two digits
+    while match self.char_at(0) {
+      Some(c) if c.is_digit(radix) => true,
+      Some('_') => matches!(self.char_at(1), Some(c) if c.is_digit(radix)),
+      _ => false,
+    } {
+      self.cursor += 1;
+    }
+  }
+
   fn scan_number(&mut self) -> Token {
     let start = self.cursor;
 
-    while self.cursor < self.source.len()
-      && self.source[self.cursor].is_ascii_digit()
-    {
-      self.cursor += 1;
+    let radix = match (self.char_at(0), self.char_at(1)) {
+      (Some('0'), Some('x')) => 16,
+      (Some('0'), Some('o')) => 8,
+      (Some('0'), Some('b')) => 2,
+      _ => 10,
+    };
+
+    if radix != 10 {
+      self.cursor += 2;
+      self.scan_digits(radix);
+
+      let lexeme = self.make_lexeme(start, self.cursor);
+      let digits = lexeme[2..].replace('_', "");
+      if digits.is_empty() {
+        panic!("expected digits after '{}'", lexeme);
+      }
+
+      let value = i64::from_str_radix(&digits, radix).unwrap_or_else(|_| {
+        panic!("integer literal '{}' doesn't fit in 64 bits", lexeme)
+      });
+
+      return Token { lexeme, kind: TokenKind::Int(value) };
     }
 
-    if self.cursor < self.source.len()
-      && self.source[self.cursor] == '.'
-      && self.source[self.cursor + 1].is_ascii_digit()
+    self.scan_digits(10);
+
+    let mut is_float = false;
+
+    if matches!(self.char_at(0), Some('.'))
+      && matches!(self.char_at(1), Some(c) if c.is_ascii_digit())
     {
+      is_float = true;
       self.cursor += 1;
+      self.scan_digits(10);
+    }
 
-      while self.cursor < self.source.len()
-        && self.source[self.cursor].is_ascii_digit()
-      {
-        self.cursor += 1;
-      }
+    let exponent_len = match (self.char_at(1), self.char_at(2)) {
+      (Some(c), _) if c.is_ascii_digit() => 1,
+      (Some('+') | Some('-'), Some(c)) if c.is_ascii_digit() => 2,
+      _ => 0,
+    };
+
+    if matches!(self.char_at(0), Some('e') | Some('E')) && exponent_len > 0 {
+      is_float = true;
+      self.cursor += 1 + exponent_len;
+      self.scan_digits(10);
     }
 
     let lexeme = self.make_lexeme(start, self.cursor);
-    let value = lexeme.parse().expect("could not parse number literal");
+    let digits = lexeme.replace('_', "");
+
+    // Integer literals that don't fit in 64 bits fall back to floats
+    let kind = match digits.parse() {
+      Ok(value) if !is_float => TokenKind::Int(value),
+      _ =>
+        TokenKind::Num(digits.parse().expect("could not parse number literal")),
+    };
 
-    Token { lexeme, kind: TokenKind::Num(value) }
+    Token { lexeme, kind }
   }
 
   fn scan_word(&mut self) -> Token {
@@ -206,6 +253,7 @@ impl RawLexer {
       "continue" => Some(TokenKind::Continue),
       "let" => Some(TokenKind::Let),
       "exp" => Some(TokenKind::Exp),
+      "withEnv" => Some(TokenKind::WithEnv),
       "true" => Some(TokenKind::True),
       "false" => Some(TokenKind::False),
       "nil" => Some(TokenKind::Nil),
@@ -330,6 +378,77 @@ impl RawLexer {
     first
   }
 
+  // Comment lines starting a line right above `fn name` document the function,
+  // every other comment is thrown away
+  fn scan_doc_comment(&mut self) -> Option<Token> {
+    let start = self.cursor;
+    let mut lines = Vec::new();
+
+    loop {
+      let from = self.cursor + 1;
+      self.consume_comment();
+      lines.push(self.make_lexeme(from, self.cursor).trim().to_string());
+
+      let mut length = 0;
+      if let Some('\r') = self.char_at(length) {
+        length += 1;
+      }
+      if let Some('\n') = self.char_at(length) {
+        length += 1;
+      } else {
+        break;
+      }
+      while let Some(' ') | Some('\t') = self.char_at(length) {
+        length += 1;
+      }
+
+      match self.char_at(length) {
+        Some('#') => self.cursor += length,
+        Some('f') | Some('e') if self.is_fn_at(length) => {
+          self.cursor += length;
+          return Some(Token {
+            kind:   TokenKind::DocComment(lines.join("\n")),
+            lexeme: self.make_lexeme(start, self.cursor),
+          });
+        }
+        _ => break,
+      }
+    }
+
+    // Not followed by a function, so only the first line is skipped
+    self.cursor = start;
+    self.consume_comment();
+    None
+  }
+
+  // Whether a function declaration starts at the offset, exported or not.
+  // Lambdas have no name, and they're documented by the comment above the
+  // statement they're in if anything
+  fn is_fn_at(&self, mut offset: usize) -> bool {
+    let is_word_at = |offset: usize, word: &str| {
+      word.chars().enumerate().all(|(i, c)| self.char_at(offset + i) == Some(c))
+        && matches!(self.char_at(offset + word.len()), Some(' ') | Some('\t'))
+    };
+
+    if is_word_at(offset, "exp") {
+      offset += "exp".len();
+      while let Some(' ') | Some('\t') = self.char_at(offset) {
+        offset += 1;
+      }
+    }
+
+    if !is_word_at(offset, "fn") {
+      return false;
+    }
+
+    offset += "fn".len();
+    while let Some(' ') | Some('\t') = self.char_at(offset) {
+      offset += 1;
+    }
+
+    self.char_at(offset).map_or(false, can_start_word)
+  }
+
   fn consume_comment(&mut self) {
     self.cursor += 1;
     while self.cursor < self.source.len()
@@ -347,11 +466,18 @@ impl Iterator for RawLexer {
     let token = if !self.buffer.is_empty() {
       Some(self.buffer.remove(0))
     } else {
-      if matches!(self.char_at(0), Some('#')) {
-        self.consume_comment();
-      }
+      let doc = match self.char_at(0) {
+        Some('#') if self.is_new_line => self.scan_doc_comment(),
+        Some('#') => {
+          self.consume_comment();
+          None
+        }
+        _ => None,
+      };
 
       match (self.char_at(0), self.char_at(1)) {
+        _ if doc.is_some() => doc,
+
         (None, _) => None,
 
         (Some('}'), _) if self.interp_count > 0 && self.braces_count == 0 =>
@@ -369,7 +495,9 @@ impl Iterator for RawLexer {
     };
 
     match token {
-      Some(Token { kind: TokenKind::Newline, .. }) => self.is_new_line = true,
+      Some(Token { kind: TokenKind::Newline, .. })
+      | Some(Token { kind: TokenKind::DocComment(_), .. }) =>
+        self.is_new_line = true,
       Some(Token { kind: TokenKind::Space, .. }) => (),
       _ => self.is_new_line = false,
     }
@@ -54,6 +54,49 @@ fn scans_identifiers() {
   }]);
 }
 
+#[test]
+fn scans_integer_literals() {
+  assert_eq!(scan("0xff 0o17 0b101 9223372036854775808"), vec![
+    Token { kind: TokenKind::Int(255), lexeme: "0xff".to_owned() },
+    Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
+    Token { kind: TokenKind::Int(15), lexeme: "0o17".to_owned() },
+    Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
+    Token { kind: TokenKind::Int(5), lexeme: "0b101".to_owned() },
+    Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
+    Token {
+      kind:   TokenKind::Num(9223372036854775808.0),
+      lexeme: "9223372036854775808".to_owned(),
+    }
+  ]);
+}
+
+#[test]
+#[should_panic(expected = "expected digits after '0x'")]
+fn panics_on_prefix_without_digits() { scan("0x"); }
+
+#[test]
+#[should_panic(expected = "integer literal '0xfffffffffffffffff' doesn't fit")]
+fn panics_on_too_big_integer_literal() { scan("0xfffffffffffffffff"); }
+
+#[test]
+fn scans_exponents_and_separators() {
+  assert_eq!(scan("1e9 2.5E-3 1_000_000 0xff_ff 1_"), vec![
+    Token { kind: TokenKind::Num(1e9), lexeme: "1e9".to_owned() },
+    Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
+    Token { kind: TokenKind::Num(2.5e-3), lexeme: "2.5E-3".to_owned() },
+    Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
+    Token { kind: TokenKind::Int(1_000_000), lexeme: "1_000_000".to_owned() },
+    Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
+    Token { kind: TokenKind::Int(0xffff), lexeme: "0xff_ff".to_owned() },
+    Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
+    Token { kind: TokenKind::Int(1), lexeme: "1".to_owned() },
+    Token {
+      kind:   TokenKind::Identifier("_".to_owned()),
+      lexeme: "_".to_owned(),
+    },
+  ]);
+}
+
 #[test]
 fn scans_simple_string_literal() {
   assert_eq!(scan("\"hello world\""), vec![Token {
@@ -79,7 +122,7 @@ fn scans_string_literal_with_escape_chars() {
 #[test]
 fn scans_number_literals() {
   assert_eq!(scan("12 3.14 .5"), vec![
-    Token { kind: TokenKind::Num(12.0), lexeme: "12".to_owned() },
+    Token { kind: TokenKind::Int(12), lexeme: "12".to_owned() },
     Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
     Token { kind: TokenKind::Num(3.14), lexeme: "3.14".to_owned() },
     Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
@@ -200,7 +243,7 @@ fn scans_interpolated_string_dict() {
       lexeme: "x".to_owned(),
     },
     Token { kind: TokenKind::Colon, lexeme: ":".to_owned() },
-    Token { kind: TokenKind::Num(1.0), lexeme: "1".to_owned() },
+    Token { kind: TokenKind::Int(1), lexeme: "1".to_owned() },
     Token { kind: TokenKind::RightBrace, lexeme: "}".to_owned() },
     Token {
       kind:   TokenKind::String {
@@ -559,6 +602,49 @@ fn ignores_comments() {
   ]);
 }
 
+#[test]
+fn keeps_doc_comments() {
+  assert_eq!(scan("# Runs\n# tests\nfn x# y\n"), vec![
+    Token {
+      kind:   TokenKind::DocComment("Runs\ntests".to_string()),
+      lexeme: "# Runs\n# tests\n".to_string(),
+    },
+    Token { kind: TokenKind::Fn, lexeme: "fn".to_owned() },
+    Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
+    Token {
+      kind:   TokenKind::Identifier("x".to_string()),
+      lexeme: "x".to_string(),
+    },
+    Token { kind: TokenKind::Newline, lexeme: "\n".to_owned() },
+  ]);
+}
+
+#[test]
+fn keeps_doc_comments_of_exported_functions() {
+  assert_eq!(scan("# Runs\nexp  fn x"), vec![
+    Token {
+      kind:   TokenKind::DocComment("Runs".to_string()),
+      lexeme: "# Runs\n".to_string(),
+    },
+    Token { kind: TokenKind::Exp, lexeme: "exp".to_owned() },
+    Token { kind: TokenKind::Space, lexeme: "  ".to_owned() },
+    Token { kind: TokenKind::Fn, lexeme: "fn".to_owned() },
+    Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
+    Token {
+      kind:   TokenKind::Identifier("x".to_string()),
+      lexeme: "x".to_string(),
+    },
+  ]);
+}
+
+#[test]
+fn skips_comments_above_lambdas() {
+  assert_eq!(scan("# x\nfn (a)")[0], Token {
+    kind:   TokenKind::Newline,
+    lexeme: "\n".to_owned(),
+  });
+}
+
 #[test]
 fn crlf_line_ending() {
   assert_eq!(scan("x\r\ny"), vec![
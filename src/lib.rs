@@ -0,0 +1,23 @@
+// Koi as a library, to run scripts from within other programs:
+//
+//   let mut interpreter = koi::Interpreter::new();
+//   interpreter.run_source("fn greet(name) {\n  return 'hi ' + name\n}");
+//   let greeting =
+//     interpreter.call_fn("greet", vec![koi::Value::String("koi".into())]);
+//
+// Errors in scripts are reported by panicking, like the `koi` binary does.
+// Hosts that need to carry on afterwards can use `try_run` and `try_eval`,
+// which return them instead.
+
+// Dicts are `IndexMap`s, which embedders can build with this very version
+pub use {
+  indexmap,
+  interp::{FromValue, Interpreter, IntoValue, Value},
+};
+
+mod ast;
+// Parts of the interpreter are there only for the repl and tasks of `koi`
+#[allow(dead_code, unused_imports)] mod interp;
+mod lexer;
+mod parser;
+mod token;
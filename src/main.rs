@@ -1,7 +1,5 @@
-#![feature(test)]
-
 use {
-  crate::lexer::new as new_lexer,
+  crate::{interp::Interpreter, lexer::new as new_lexer, parser::Parser},
   clap::{App, Arg},
   itertools::Itertools,
   std::{
@@ -11,10 +9,15 @@ use {
   },
 };
 
+// The binary is built from the same sources as the library, as the repl and
+// tasks need more of the interpreter than the library exposes
 mod ast;
-mod interp;
+// Parts of the interpreter are there only for embedders
+#[allow(dead_code, unused_imports)] mod interp;
 mod lexer;
 mod parser;
+mod repl;
+mod task;
 mod token;
 
 fn split_args() -> (Vec<String>, Vec<String>) {
@@ -35,17 +38,37 @@ fn split_args() -> (Vec<String>, Vec<String>) {
     }
 
     if mode == "make" {
-      if input.len() == 0 {
-        flags.insert(0, "all".to_string());
+      flags.insert(0, "./Koifile".to_string());
+      flags.insert(0, "koi".to_string());
+
+      // Options like in `koix -j 4 build` come before the tasks
+      let mut input = input.into_iter().peekable();
+      while let Some(arg) = input.next_if(|arg| arg.starts_with('-')) {
+        let takes_value = arg == "-j" || arg == "--jobs";
+        flags.push(arg);
+        if takes_value {
+          flags.extend(input.next());
+        }
       }
 
-      flags.insert(0, "-f".to_string());
-      flags.insert(0, "./Koifile".to_string());
-      flags.append(&mut input);
+      let mut tasks = input.collect_vec();
+      if tasks.is_empty() && !flags.iter().any(|flag| flag == "--list") {
+        tasks.push("all".to_string());
+      }
+
+      // Task words go last, as `-f` takes all the values after it
+      if !tasks.is_empty() {
+        flags.push("-f".to_string());
+        flags.append(&mut tasks);
+      }
 
       return (flags, vec![]);
     }
 
+    // Clap takes the first arg as the program name, so without one in front
+    // the first flag after `--` would be dropped
+    flags.insert(0, "koi".to_string());
+
     (flags, input)
   } else {
     let args = env::args().collect_vec();
@@ -79,16 +102,81 @@ fn main() {
         .help("Read script from stdin.")
         .conflicts_with("path"),
     )
+    .arg(
+      Arg::with_name("command")
+        .short("c")
+        .long("command")
+        .value_name("SOURCE")
+        .takes_value(true)
+        .help("Run the given source instead of a file.")
+        .conflicts_with_all(&["path", "stdin"]),
+    )
+    .arg(
+      Arg::with_name("repl")
+        .short("r")
+        .long("repl")
+        .takes_value(false)
+        .help("Start an interactive prompt.")
+        .conflicts_with_all(&["path", "stdin", "command"]),
+    )
     .arg(
       Arg::with_name("fn")
         .short("f")
         .long("--fn")
+        .value_name("TASK")
         .takes_value(true)
-        .help("Function to call."),
+        .multiple(true)
+        .allow_hyphen_values(true)
+        .help("Functions to call, each followed by its arguments."),
+    )
+    .arg(
+      Arg::with_name("list")
+        .short("l")
+        .long("list")
+        .takes_value(false)
+        .help("List the functions that can be called with -f.")
+        .conflicts_with_all(&["repl", "fn"]),
+    )
+    .arg(
+      Arg::with_name("jobs")
+        .short("j")
+        .long("jobs")
+        .value_name("N")
+        .takes_value(true)
+        .help("Run up to N functions called with -f at once."),
+    )
+    .arg(
+      Arg::with_name("keep-going")
+        .short("k")
+        .long("keep-going")
+        .takes_value(false)
+        .help("Keep starting functions after one fails.")
+        .requires("jobs"),
     )
     .get_matches_from(koi_args);
 
-  let source = if matches.is_present("stdin") {
+  // Without a script to run there's nothing else to do but be interactive.
+  // Asking for tasks means a script is expected, even without a Koifile
+  let is_repl = matches.is_present("repl")
+    || !(matches.is_present("path")
+      || matches.is_present("stdin")
+      || matches.is_present("command")
+      || matches.is_present("list")
+      || matches.is_present("fn")
+      || matches.is_present("jobs")
+      || matches.is_present("keep-going")
+      || PathBuf::from("Koifile").exists());
+
+  if is_repl {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_args(script_args);
+    repl::run(interpreter);
+    return;
+  }
+
+  let source = if let Some(command) = matches.value_of("command") {
+    command.to_string()
+  } else if matches.is_present("stdin") {
     let mut buffer = String::new();
     io::stdin().read_to_string(&mut buffer).unwrap();
     buffer
@@ -99,27 +187,51 @@ fn main() {
     )
   };
 
-  let lexer = new_lexer(source);
-
-  let mut parser = parser::Parser::new(lexer);
-  let prog = parser.parse();
+  let prog = Parser::new(new_lexer(source.clone())).parse();
 
-  let mut interpreter = interp::Interpreter::new();
-  interpreter.set_args(script_args);
-  if let Some(path) = matches.value_of("path") {
-    let mut import_root = std::fs::canonicalize(PathBuf::from(path))
-      .expect("couldn't set import root");
-    import_root.pop();
-    interpreter.set_import_root(import_root);
+  if matches.is_present("list") {
+    print!("{}", task::list(&prog));
+    return;
   }
-  interpreter.run(prog);
 
-  if let Some(f) = matches.value_of("fn") {
-    use ast::{Expr, Stmt};
+  let targets = task::targets(&prog);
+
+  let path = matches.value_of("path").map(|path| {
+    std::fs::canonicalize(PathBuf::from(path))
+      .expect("couldn't set import root")
+  });
+  let import_root =
+    path.as_ref().map(|path| path.parent().unwrap().to_path_buf());
+
+  let mut interpreter = Interpreter::new();
+  interpreter.set_args(script_args.clone());
+  if let Some(import_root) = &import_root {
+    interpreter.set_import_root(import_root.clone());
+  }
+  match path {
+    Some(path) => interpreter.run_entry(path, prog),
+    None => interpreter.run(prog),
+  }
 
-    interpreter.run(vec![Stmt::Expr(Expr::Call {
-      func: Box::new(Expr::Get(f.to_string())),
-      args: vec![],
-    })]);
+  if let Some(words) = matches.values_of("fn") {
+    let words = words.map(String::from).collect_vec();
+
+    if let Some(jobs) = matches.value_of("jobs") {
+      let jobs = match jobs.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => jobs,
+        _ => panic!("expected a positive number of jobs"),
+      };
+
+      task::run_parallel(
+        &mut interpreter,
+        &targets,
+        &words,
+        task::Script { source, import_root },
+        jobs,
+        matches.is_present("keep-going"),
+      );
+    } else {
+      task::run(&mut interpreter, &targets, &words);
+    }
   }
 }
@@ -76,6 +76,7 @@ impl Parser {
   }
 
   fn parse_cmd_atom(&mut self) -> Cmd {
+    let mut env = Vec::new();
     let mut segments = Vec::new();
 
     loop {
@@ -83,55 +84,94 @@ impl Parser {
 
       self.lexer.consume_whitespace(self.is_multiline);
 
-      loop {
-        if self.lexer.peek().is_none() {
-          break;
-        }
+      // Variables can be assigned before the command, as in `FOO=bar cmd`
+      if segments.is_empty() {
+        if let Some(Token { kind: TokenKind::Identifier(name), .. }) =
+          self.lexer.peek().cloned()
+        {
+          let lexeme = self.lexer.next().unwrap().lexeme;
 
-        if {
-          let t = self.lexer.peek().unwrap();
-          t.is_cmd_op()
-            || [TokenKind::Space, TokenKind::Newline, TokenKind::RightParen]
-              .contains(&t.kind)
-        } {
-          break;
-        }
+          if let Some(Token { kind: TokenKind::Equal, .. }) = self.lexer.peek()
+          {
+            self.lexer.next();
 
-        let expr = match self.lexer.next().unwrap() {
-          t @ Token { kind: TokenKind::String { .. }, .. } =>
-            self.continue_parse_string_expr(t),
-          Token { kind: TokenKind::LeftBrace, .. } => {
-            self.lexer.consume_whitespace(self.is_multiline);
-            let expr = self.parse_expr(0);
-            self.lexer.consume_whitespace(self.is_multiline);
-
-            if !matches!(
-              self.lexer.next(),
-              Some(Token { kind: TokenKind::RightBrace, .. })
-            ) {
-              panic!("expected right brace");
+            let value = self.parse_cmd_word(Vec::new());
+            if value.is_empty() {
+              panic!("expected value for variable '{}'", name);
             }
 
-            expr
+            env.push((name, value));
+            continue;
           }
-          t => Expr::Literal(Value::String(t.lexeme)),
-        };
 
-        exprs.push(expr);
+          exprs.push(Expr::Literal(Value::String(lexeme)));
+        }
       }
 
-      if exprs.len() > 0 {
+      let exprs = self.parse_cmd_word(exprs);
+
+      if !exprs.is_empty() {
         segments.push(exprs);
       } else {
         break;
       }
     }
 
-    if segments.len() == 0 {
+    if segments.is_empty() {
+      if !env.is_empty() {
+        panic!("expected command after variable assignments");
+      }
+
       panic!("empty command");
     }
 
-    Cmd::Atom(segments)
+    if env.is_empty() {
+      Cmd::Atom(segments)
+    } else {
+      Cmd::Env(env, Box::new(Cmd::Atom(segments)))
+    }
+  }
+
+  fn parse_cmd_word(&mut self, mut exprs: Vec<Expr>) -> Vec<Expr> {
+    loop {
+      if self.lexer.peek().is_none() {
+        break;
+      }
+
+      let is_word_end = {
+        let t = self.lexer.peek().unwrap();
+        t.is_cmd_op()
+          || [TokenKind::Space, TokenKind::Newline, TokenKind::RightParen]
+            .contains(&t.kind)
+      };
+      if is_word_end {
+        break;
+      }
+
+      let expr = match self.lexer.next().unwrap() {
+        t @ Token { kind: TokenKind::String { .. }, .. } =>
+          self.continue_parse_string_expr(t),
+        Token { kind: TokenKind::LeftBrace, .. } => {
+          self.lexer.consume_whitespace(self.is_multiline);
+          let expr = self.parse_expr(0);
+          self.lexer.consume_whitespace(self.is_multiline);
+
+          if !matches!(
+            self.lexer.next(),
+            Some(Token { kind: TokenKind::RightBrace, .. })
+          ) {
+            panic!("expected right brace");
+          }
+
+          expr
+        }
+        t => Expr::Literal(Value::String(t.lexeme)),
+      };
+
+      exprs.push(expr);
+    }
+
+    exprs
   }
 }
 
@@ -5,12 +5,14 @@ use {
     interp::Value,
     token::{Token, TokenKind},
   },
-  std::collections::HashMap,
+  indexmap::IndexMap,
 };
 
 impl Parser {
   pub fn parse_expr(&mut self, min_bp: u8) -> Expr {
     let mut lhs = match self.lexer.next() {
+      Some(Token { kind: TokenKind::Int(int), .. }) =>
+        Expr::Literal(Value::Int(int)),
       Some(Token { kind: TokenKind::Num(num), .. }) =>
         Expr::Literal(Value::Num(num)),
       Some(Token { kind: TokenKind::Identifier(name), .. }) => Expr::Get(name),
@@ -131,6 +133,10 @@ impl Parser {
             self.lexer.consume_whitespace(self.is_multiline);
             let name = match self.lexer.next() {
               Some(Token { kind: TokenKind::Identifier(name), .. }) => name,
+              // Keywords are valid field names, as in `math.exp`
+              Some(Token { lexeme, .. })
+                if lexeme.starts_with(|c: char| c.is_ascii_alphabetic()) =>
+                lexeme,
               _ => panic!("expected identifier"),
             };
 
@@ -146,7 +152,7 @@ impl Parser {
         continue;
       }
 
-      if let Some((l_bp, r_bp)) = infix_binding_power(&op) {
+      if let Some((l_bp, r_bp)) = infix_binding_power(op) {
         if l_bp < min_bp {
           break;
         }
@@ -221,7 +227,7 @@ impl Parser {
   }
 
   fn parse_dict_literal(&mut self) -> Expr {
-    let mut dict = HashMap::new();
+    let mut dict = IndexMap::new();
 
     loop {
       self.lexer.consume_whitespace(self.is_multiline);
@@ -239,6 +245,7 @@ impl Parser {
           kind: TokenKind::String { value, does_interp }, ..
         }) if !does_interp => value,
         Some(Token { kind: TokenKind::Identifier(name), .. }) => name,
+        Some(Token { kind: TokenKind::Int(int), .. }) => int.to_string(),
         Some(Token { kind: TokenKind::Num(num), .. }) => num.to_string(),
         _ => panic!("bad dict key"),
       };
@@ -327,9 +334,15 @@ fn make_infix_expr(lhs: Expr, op: &TokenKind, rhs: Expr) -> Expr {
     TokenKind::Minus => Expr::Binary(lhs, BinaryOp::Sub, rhs),
     TokenKind::Star => Expr::Binary(lhs, BinaryOp::Mul, rhs),
     TokenKind::Slash => Expr::Binary(lhs, BinaryOp::Div, rhs),
+    TokenKind::SlashSlash => Expr::Binary(lhs, BinaryOp::IntDiv, rhs),
     TokenKind::Perc => Expr::Binary(lhs, BinaryOp::Mod, rhs),
     TokenKind::Caret => Expr::Binary(lhs, BinaryOp::Pow, rhs),
 
+    TokenKind::Amper => Expr::Binary(lhs, BinaryOp::BitAnd, rhs),
+    TokenKind::Pipe => Expr::Binary(lhs, BinaryOp::BitOr, rhs),
+    TokenKind::LessLess => Expr::Binary(lhs, BinaryOp::Shl, rhs),
+    TokenKind::GreatGreat => Expr::Binary(lhs, BinaryOp::Shr, rhs),
+
     TokenKind::AmperAmper => Expr::Binary(lhs, BinaryOp::And, rhs),
     TokenKind::PipePipe => Expr::Binary(lhs, BinaryOp::Or, rhs),
 
@@ -409,7 +422,7 @@ fn make_infix_expr(lhs: Expr, op: &TokenKind, rhs: Expr) -> Expr {
 fn prefix_binding_power(op: &TokenKind) -> Option<((), u8)> {
   use TokenKind::*;
   let bp = match op {
-    Bang | Plus | Minus => ((), 17),
+    Bang | Plus | Minus => ((), 25),
     _ => return None,
   };
   Some(bp)
@@ -418,10 +431,13 @@ fn prefix_binding_power(op: &TokenKind) -> Option<((), u8)> {
 fn infix_binding_power(op: &TokenKind) -> Option<(u8, u8)> {
   use TokenKind::*;
   let bp = match op {
-    Caret => (16, 15),
-    Star | Slash | Perc => (13, 14),
-    Plus | Minus => (11, 12),
-    Great | GreatEqual | Less | LessEqual => (9, 10),
+    Caret => (24, 23),
+    Star | Slash | SlashSlash | Perc => (21, 22),
+    Plus | Minus => (19, 20),
+    LessLess | GreatGreat => (17, 18),
+    Amper => (15, 16),
+    Pipe => (13, 14),
+    Great | GreatEqual | Less | LessEqual => (11, 12),
     EqualEqual | BangEqual => (7, 8),
     AmperAmper => (5, 6),
     PipePipe => (3, 4),
@@ -435,7 +451,7 @@ fn infix_binding_power(op: &TokenKind) -> Option<(u8, u8)> {
 fn postfix_binding_power(op: &TokenKind) -> Option<(u8, ())> {
   use TokenKind::*;
   let bp = match op {
-    LeftBracket | LeftParen | Dot => (19, ()),
+    LeftBracket | LeftParen | Dot => (27, ()),
     _ => return None,
   };
   Some(bp)
@@ -8,6 +8,15 @@ use {
 
 impl Parser {
   pub fn continue_parse_fn(&mut self) -> Func {
+    let params = self.parse_params();
+
+    self.lexer.consume_whitespace(self.is_multiline);
+    let body = self.parse_block();
+
+    Func::User { name: None, params, body: Box::new(body), captured_env: None }
+  }
+
+  pub fn parse_params(&mut self) -> Vec<String> {
     let mut params = Vec::new();
 
     if !matches!(
@@ -37,9 +46,6 @@ impl Parser {
       }
     }
 
-    self.lexer.consume_whitespace(self.is_multiline);
-    let body = self.parse_block();
-
-    Func::User { name: None, params, body: Box::new(body), captured_env: None }
+    params
   }
 }
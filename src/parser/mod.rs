@@ -1,5 +1,5 @@
 use crate::{
-  ast::{Prog, Stmt},
+  ast::{Expr, Prog, Stmt},
   lexer::Lexer,
   token::{Token, TokenKind},
 };
@@ -21,6 +21,19 @@ impl Parser {
 
   pub fn parse(&mut self) -> Prog { self.parse_stmts() }
 
+  // Parses source made of a single expression
+  pub fn parse_single_expr(&mut self) -> Expr {
+    self.lexer.consume_whitespace(true);
+    let expr = self.parse_expr(0);
+    self.lexer.consume_whitespace(true);
+
+    if !self.is_at_end() {
+      panic!("expected end of input after expression");
+    }
+
+    expr
+  }
+
   fn parse_stmts(&mut self) -> Vec<Stmt> {
     let mut stmts = Vec::new();
 
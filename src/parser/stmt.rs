@@ -1,7 +1,7 @@
 use {
   super::Parser,
   crate::{
-    ast::{Expr, Stmt},
+    ast::{Expr, Stmt, Target},
     interp::Func,
     token::{Token, TokenKind},
   },
@@ -14,13 +14,33 @@ impl Parser {
 
       Some(Token { kind: TokenKind::LeftBrace, .. }) => self.parse_block(),
 
-      Some(Token { kind: TokenKind::Let, .. })
-      | Some(Token { kind: TokenKind::Exp, .. }) => self.parse_let_stmt(),
+      Some(Token { kind: TokenKind::Let, .. }) => self.parse_let_stmt(false),
+      Some(Token { kind: TokenKind::Exp, .. }) => self.parse_exp_stmt(),
 
       Some(Token { kind: TokenKind::If, .. }) => self.parse_if_stmt(),
       Some(Token { kind: TokenKind::For, .. }) => self.parse_for_stmt(),
       Some(Token { kind: TokenKind::While, .. }) => self.parse_while_stmt(),
-      Some(Token { kind: TokenKind::Fn, .. }) => self.parse_fn_stmt(),
+      Some(Token { kind: TokenKind::WithEnv, .. }) =>
+        self.parse_with_env_stmt(),
+      Some(Token { kind: TokenKind::Fn, .. }) =>
+        self.parse_fn_stmt(None, false),
+      Some(Token { kind: TokenKind::DocComment(_), .. }) => {
+        let doc = match self.lexer.next() {
+          Some(Token { kind: TokenKind::DocComment(doc), .. }) => doc,
+          _ => unreachable!(),
+        };
+
+        // The lexer only keeps comments that are followed by a function,
+        // which may be exported
+        let is_exp =
+          matches!(self.lexer.peek(), Some(Token { kind: TokenKind::Exp, .. }));
+        if is_exp {
+          self.lexer.next();
+          self.lexer.consume_whitespace(self.is_multiline);
+        }
+
+        self.parse_fn_stmt(Some(doc), is_exp)
+      }
 
       Some(Token { kind: TokenKind::Return, .. }) => self.parse_return(),
 
@@ -94,46 +114,20 @@ impl Parser {
       .lexer
       .by_ref()
       .take_while(|t| t.kind != TokenKind::Newline)
-      .filter(|t| t.kind != TokenKind::Space)
       .collect::<Vec<Token>>();
 
     self.lexer.stop_recording(true);
 
-    let mut line_tokens_iter = line_tokens.iter().peekable();
-
-    loop {
-      if !matches!(
-        line_tokens_iter.next(),
-        Some(Token { kind: TokenKind::Identifier(..), .. })
-      ) {
-        return false;
-      }
+    if starts_cmd_env(&line_tokens) {
+      return false;
+    }
 
-      if matches!(
-        line_tokens_iter.peek(),
-        Some(&Token { kind: TokenKind::Dot, .. })
-      ) {
-        line_tokens_iter.next();
-        continue;
-      }
+    let line_tokens = line_tokens
+      .into_iter()
+      .filter(|t| t.kind != TokenKind::Space)
+      .collect::<Vec<Token>>();
 
-      use TokenKind::*;
-      return matches!(
-        line_tokens_iter.next(),
-        Some(Token {
-          kind: LeftParen
-            | LeftBracket
-            | Equal
-            | PlusEqual
-            | MinusEqual
-            | StarEqual
-            | SlashEqual
-            | CaretEqual
-            | PercEqual,
-          ..
-        })
-      );
-    }
+    starts_expr_stmt(&line_tokens)
   }
 
   fn parse_import(&mut self) -> Stmt {
@@ -142,27 +136,43 @@ impl Parser {
 
     self.lexer.consume_whitespace(self.is_multiline);
 
-    match self.lexer.next() {
+    let path = match self.lexer.next() {
       Some(Token {
         kind: TokenKind::String { does_interp: false, value },
         ..
-      }) => Stmt::Import(value),
+      }) => value,
       _ => panic!("expected a file to import"),
-    }
+    };
+
+    // `as` isn't a keyword, so that it can still be used as a name elsewhere
+    self.lexer.consume_whitespace(false);
+    let alias = match self.lexer.peek() {
+      Some(Token { kind: TokenKind::Identifier(word), .. }) if word == "as" => {
+        self.lexer.next();
+        self.lexer.consume_whitespace(false);
+        Some(self.must_identifier())
+      }
+      _ => None,
+    };
+
+    Stmt::Import { path, alias }
   }
 
-  fn parse_let_stmt(&mut self) -> Stmt {
-    let is_exp = self.lexer.peek().unwrap().kind == TokenKind::Exp;
+  // Exported variables are passed to commands, while exported functions are
+  // members of the module when the file is imported with a name
+  fn parse_exp_stmt(&mut self) -> Stmt {
+    self.lexer.next();
+    self.lexer.consume_whitespace(self.is_multiline);
 
-    if is_exp {
-      self.lexer.next();
-      self.lexer.consume_whitespace(self.is_multiline);
+    match self.lexer.peek() {
+      Some(Token { kind: TokenKind::Fn, .. }) => self.parse_fn_stmt(None, true),
+      _ => self.parse_let_stmt(true),
     }
+  }
 
-    // Only meaningful if there was an `exp`. Otherwise this has already been
-    // checked by `parse_stmt`
+  fn parse_let_stmt(&mut self, is_exp: bool) -> Stmt {
     if !matches!(self.lexer.next(), Some(Token { kind: TokenKind::Let, .. })) {
-      panic!("expected let");
+      panic!("expected let or fn after exp");
     }
 
     self.lexer.consume_whitespace(self.is_multiline);
@@ -271,25 +281,123 @@ impl Parser {
     Stmt::While { cond, then_do: Box::new(then_do) }
   }
 
-  fn parse_fn_stmt(&mut self) -> Stmt {
+  fn parse_with_env_stmt(&mut self) -> Stmt {
+    self.lexer.next();
+
+    self.lexer.consume_whitespace(self.is_multiline);
+    if !matches!(
+      self.lexer.next(),
+      Some(Token { kind: TokenKind::LeftParen, .. })
+    ) {
+      panic!("expected left parenthesis");
+    }
+
+    self.lexer.consume_whitespace(true);
+    let env = self.parse_expr(0);
+    self.lexer.consume_whitespace(true);
+
+    let opts =
+      if let Some(Token { kind: TokenKind::Comma, .. }) = self.lexer.peek() {
+        self.lexer.next();
+
+        self.lexer.consume_whitespace(true);
+        let opts = self.parse_expr(0);
+        self.lexer.consume_whitespace(true);
+
+        Some(opts)
+      } else {
+        None
+      };
+
+    if !matches!(
+      self.lexer.next(),
+      Some(Token { kind: TokenKind::RightParen, .. })
+    ) {
+      panic!("expected right parenthesis");
+    }
+
+    self.lexer.consume_whitespace(self.is_multiline);
+    let body = self.parse_block();
+
+    Stmt::WithEnv { env, opts, body: Box::new(body) }
+  }
+
+  fn parse_fn_stmt(&mut self, doc: Option<String>, is_exp: bool) -> Stmt {
     self.lexer.next();
 
     self.lexer.consume_whitespace(self.is_multiline);
     let name = self.must_identifier();
 
     self.lexer.consume_whitespace(self.is_multiline);
+    let params = self.parse_params();
+
+    self.lexer.consume_whitespace(self.is_multiline);
+    let target = self.parse_target();
 
-    let (params, body) = match self.continue_parse_fn() {
-      Func::User { params, body, .. } => (params, body),
-      _ => unreachable!(),
+    self.lexer.consume_whitespace(self.is_multiline);
+    let body = self.parse_block();
+
+    let func = Func::User {
+      name: Some(name),
+      params,
+      body: Box::new(body),
+      captured_env: None,
     };
 
-    let func =
-      Func::User { name: Some(name), params, body, captured_env: None };
+    Stmt::Func { func, doc, target, is_exp }
+  }
+
+  // Functions to run before this one and input globs when called as a task,
+  // followed by the files it outputs
+  fn parse_target(&mut self) -> Target {
+    let mut target = Target::default();
 
-    Stmt::Func(func)
+    if matches!(self.lexer.peek(), Some(Token { kind: TokenKind::Colon, .. })) {
+      self.lexer.next();
+
+      loop {
+        self.lexer.consume_whitespace(self.is_multiline);
+        match self.lexer.peek() {
+          Some(Token { kind: TokenKind::Identifier(_), .. }) =>
+            target.deps.push(self.must_identifier()),
+          _ => target.inputs.push(self.parse_target_path()),
+        }
+        self.lexer.consume_whitespace(self.is_multiline);
+
+        match self.lexer.peek() {
+          Some(Token { kind: TokenKind::Comma, .. }) => self.lexer.next(),
+          _ => break,
+        };
+      }
+    }
+
+    if matches!(self.lexer.peek(), Some(Token { kind: TokenKind::Minus, .. })) {
+      self.lexer.next();
+      if !matches!(
+        self.lexer.next(),
+        Some(Token { kind: TokenKind::Great, .. })
+      ) {
+        panic!("expected '->' before task outputs");
+      }
+
+      loop {
+        self.lexer.consume_whitespace(self.is_multiline);
+        target.outputs.push(self.parse_target_path());
+        self.lexer.consume_whitespace(self.is_multiline);
+
+        match self.lexer.peek() {
+          Some(Token { kind: TokenKind::Comma, .. }) => self.lexer.next(),
+          _ => break,
+        };
+      }
+    }
+
+    target
   }
 
+  // Paths can't be made of operations, so that `->` isn't taken as a minus
+  fn parse_target_path(&mut self) -> Expr { self.parse_expr(26) }
+
   fn parse_return(&mut self) -> Stmt {
     self.lexer.next();
 
@@ -312,3 +420,123 @@ impl Parser {
     Stmt::Return(Some(expr))
   }
 }
+
+fn starts_expr_stmt(line_tokens: &[Token]) -> bool {
+  let mut line_tokens_iter = line_tokens.iter().peekable();
+
+  loop {
+    if !matches!(
+      line_tokens_iter.next(),
+      Some(Token { kind: TokenKind::Identifier(..), .. })
+    ) {
+      return false;
+    }
+
+    if matches!(
+      line_tokens_iter.peek(),
+      Some(&Token { kind: TokenKind::Dot, .. })
+    ) {
+      line_tokens_iter.next();
+      continue;
+    }
+
+    use TokenKind::*;
+    return matches!(
+      line_tokens_iter.next(),
+      Some(Token {
+        kind: LeftParen
+          | LeftBracket
+          | Equal
+          | PlusEqual
+          | MinusEqual
+          | StarEqual
+          | SlashEqual
+          | CaretEqual
+          | PercEqual,
+        ..
+      })
+    );
+  }
+}
+
+// Detects lines like `FOO=bar cmd`, where variables are assigned without
+// spaces around the equals sign and followed by a command
+fn starts_cmd_env(line_tokens: &[Token]) -> bool {
+  let mut rest = line_tokens;
+  let mut has_env = false;
+
+  while let [
+    Token { kind: TokenKind::Identifier(..), .. },
+    Token { kind: TokenKind::Equal, .. },
+    value @ ..,
+  ] = rest
+  {
+    let value_len = cmd_word_len(value);
+    if value_len == 0 {
+      return false;
+    }
+
+    has_env = true;
+    rest = &value[value_len..];
+
+    match rest {
+      [Token { kind: TokenKind::Space, .. }, tail @ ..] => rest = tail,
+      _ => return false,
+    }
+  }
+
+  let rest = rest
+    .iter()
+    .filter(|t| t.kind != TokenKind::Space)
+    .cloned()
+    .collect::<Vec<Token>>();
+
+  use TokenKind::*;
+
+  // Something like `x=1 print(x)` is still a pair of statements
+  has_env
+    && !rest.is_empty()
+    && !matches!(
+      rest[0].kind,
+      Import
+        | For
+        | While
+        | If
+        | Fn
+        | Return
+        | Break
+        | Continue
+        | Let
+        | Exp
+        | WithEnv
+        | LeftBrace
+    )
+    && !starts_expr_stmt(&rest)
+}
+
+// Number of tokens making up the command word at the start of `tokens`,
+// keeping spaces that are inside braces or interpolated strings
+fn cmd_word_len(tokens: &[Token]) -> usize {
+  let mut depth = 0;
+
+  for (i, token) in tokens.iter().enumerate() {
+    match &token.kind {
+      TokenKind::Space | TokenKind::Newline if depth == 0 => return i,
+      TokenKind::LeftBrace => depth += 1,
+      TokenKind::RightBrace => depth -= 1,
+      TokenKind::String { does_interp, .. } => {
+        // Pieces of string after an interpolated expression start with the
+        // closing brace
+        if token.lexeme.starts_with('}') {
+          depth -= 1;
+        }
+        if *does_interp {
+          depth += 1;
+        }
+      }
+      _ => (),
+    }
+  }
+
+  tokens.len()
+}
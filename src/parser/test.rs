@@ -1,11 +1,11 @@
 use {
   super::*,
   crate::{
-    ast::{BinaryOp, Cmd, CmdOp, Expr},
+    ast::{BinaryOp, Cmd, CmdOp, Expr, Target},
     interp::{Func, Value},
     lexer::new as new_lexer,
   },
-  std::collections::HashMap,
+  indexmap::IndexMap,
 };
 
 fn make_parser(source: &str) -> Parser {
@@ -38,13 +38,17 @@ fn parses_lookups() {
       base:  Box::new(Expr::Get("foo".to_owned())),
       index: Box::new(Expr::Literal(Value::String("bar".to_owned()))),
     }),
+    ("math.exp", Expr::GetField {
+      base:  Box::new(Expr::Get("math".to_owned())),
+      index: Box::new(Expr::Literal(Value::String("exp".to_owned()))),
+    }),
     ("foo[\"bar\"]", Expr::GetField {
       base:  Box::new(Expr::Get("foo".to_owned())),
       index: Box::new(Expr::Literal(Value::String("bar".to_owned()))),
     }),
     ("foo[1]", Expr::GetField {
       base:  Box::new(Expr::Get("foo".to_owned())),
-      index: Box::new(Expr::Literal(Value::Num(1.0))),
+      index: Box::new(Expr::Literal(Value::Int(1))),
     }),
     ("foo.bar[\"baz\"].egg[\"beam\"]", Expr::GetField {
       base:  Box::new(Expr::GetField {
@@ -69,22 +73,22 @@ fn parses_assignments() {
   for (source, want) in &[
     (
       "foo=5",
-      Expr::Set("foo".to_owned(), Box::new(Expr::Literal(Value::Num(5.0)))),
+      Expr::Set("foo".to_owned(), Box::new(Expr::Literal(Value::Int(5)))),
     ),
     ("foo.bar=5", Expr::SetField {
       base:  Box::new(Expr::Get("foo".to_owned())),
       index: Box::new(Expr::Literal(Value::String("bar".to_owned()))),
-      expr:  Box::new(Expr::Literal(Value::Num(5.0))),
+      expr:  Box::new(Expr::Literal(Value::Int(5))),
     }),
     ("foo[\"bar\"]=5", Expr::SetField {
       base:  Box::new(Expr::Get("foo".to_owned())),
       index: Box::new(Expr::Literal(Value::String("bar".to_owned()))),
-      expr:  Box::new(Expr::Literal(Value::Num(5.0))),
+      expr:  Box::new(Expr::Literal(Value::Int(5))),
     }),
     ("foo[1]=5", Expr::SetField {
       base:  Box::new(Expr::Get("foo".to_owned())),
-      index: Box::new(Expr::Literal(Value::Num(1.0))),
-      expr:  Box::new(Expr::Literal(Value::Num(5.0))),
+      index: Box::new(Expr::Literal(Value::Int(1))),
+      expr:  Box::new(Expr::Literal(Value::Int(5))),
     }),
     ("foo.bar[\"baz\"].egg[\"beam\"]=5", Expr::SetField {
       base:  Box::new(Expr::GetField {
@@ -98,7 +102,7 @@ fn parses_assignments() {
         index: Box::new(Expr::Literal(Value::String("egg".to_owned()))),
       }),
       index: Box::new(Expr::Literal(Value::String("beam".to_owned()))),
-      expr:  Box::new(Expr::Literal(Value::Num(5.0))),
+      expr:  Box::new(Expr::Literal(Value::Int(5))),
     }),
   ] {
     assert_eq!(parse_expression(source), *want);
@@ -110,12 +114,32 @@ fn parses_precedence() {
   assert_eq!(
     parse_expression("1+2*3"),
     Expr::Binary(
-      Box::new(Expr::Literal(Value::Num(1.0))),
+      Box::new(Expr::Literal(Value::Int(1))),
       BinaryOp::Sum,
       Box::new(Expr::Binary(
-        Box::new(Expr::Literal(Value::Num(2.0))),
+        Box::new(Expr::Literal(Value::Int(2))),
         BinaryOp::Mul,
-        Box::new(Expr::Literal(Value::Num(3.0))),
+        Box::new(Expr::Literal(Value::Int(3))),
+      )),
+    )
+  );
+}
+
+#[test]
+fn parses_bitwise_precedence() {
+  assert_eq!(
+    parse_expression("1 | 2 & 3 << 4"),
+    Expr::Binary(
+      Box::new(Expr::Literal(Value::Int(1))),
+      BinaryOp::BitOr,
+      Box::new(Expr::Binary(
+        Box::new(Expr::Literal(Value::Int(2))),
+        BinaryOp::BitAnd,
+        Box::new(Expr::Binary(
+          Box::new(Expr::Literal(Value::Int(3))),
+          BinaryOp::Shl,
+          Box::new(Expr::Literal(Value::Int(4))),
+        )),
       )),
     )
   );
@@ -127,12 +151,12 @@ fn parses_associativity() {
     parse_expression("1+2+3"),
     Expr::Binary(
       Box::new(Expr::Binary(
-        Box::new(Expr::Literal(Value::Num(1.0))),
+        Box::new(Expr::Literal(Value::Int(1))),
         BinaryOp::Sum,
-        Box::new(Expr::Literal(Value::Num(2.0))),
+        Box::new(Expr::Literal(Value::Int(2))),
       )),
       BinaryOp::Sum,
-      Box::new(Expr::Literal(Value::Num(3.0))),
+      Box::new(Expr::Literal(Value::Int(3))),
     )
   );
 }
@@ -149,11 +173,63 @@ fn parses_cmd_stmt() {
   ]);
 }
 
+#[test]
+fn parses_cmd_stmt_with_env() {
+  assert_eq!(parse("FOO=bar BAZ={x} cmd arg"), vec![Stmt::Cmd(Cmd::Env(
+    vec![
+      ("FOO".to_owned(), vec![Expr::Literal(Value::String("bar".to_owned()))]),
+      ("BAZ".to_owned(), vec![Expr::Get("x".to_owned())]),
+    ],
+    Box::new(Cmd::Atom(vec![
+      vec![Expr::Literal(Value::String("cmd".to_owned()))],
+      vec![Expr::Literal(Value::String("arg".to_owned()))],
+    ]))
+  ))]);
+}
+
+#[test]
+fn parses_assignments_followed_by_expr_stmt() {
+  assert_eq!(parse("x=1 print(x)"), vec![
+    Stmt::Expr(Expr::Set(
+      "x".to_owned(),
+      Box::new(Expr::Literal(Value::Int(1)))
+    )),
+    Stmt::Expr(Expr::Call {
+      func: Box::new(Expr::Get("print".to_owned())),
+      args: vec![Expr::Get("x".to_owned())],
+    }),
+  ]);
+}
+
+#[test]
+fn parses_with_env_stmt() {
+  assert_eq!(parse("withEnv({FOO: 'bar'}, {clear: true}) {\n  cmd\n}"), vec![
+    Stmt::WithEnv {
+      env:  Expr::Dict(
+        vec![(
+          "FOO".to_owned(),
+          Expr::Literal(Value::String("bar".to_owned()))
+        )]
+        .into_iter()
+        .collect()
+      ),
+      opts: Some(Expr::Dict(
+        vec![("clear".to_owned(), Expr::Literal(Value::Bool(true)))]
+          .into_iter()
+          .collect()
+      )),
+      body: Box::new(Stmt::Block(vec![Stmt::Cmd(Cmd::Atom(vec![vec![
+        Expr::Literal(Value::String("cmd".to_owned()))
+      ]]))])),
+    }
+  ]);
+}
+
 #[test]
 fn parses_assignment_stmt() {
   assert_eq!(parse("foo = \n    1"), vec![Stmt::Expr(Expr::Set(
     "foo".to_owned(),
-    Box::new(Expr::Literal(Value::Num(1.0)))
+    Box::new(Expr::Literal(Value::Int(1)))
   ))]);
 }
 
@@ -184,7 +260,7 @@ fn parses_assignment_stmt_with_dots() {
   assert_eq!(parse("x.foo = 1"), vec![Stmt::Expr(Expr::SetField {
     base:  Box::new(Expr::Get("x".to_owned())),
     index: Box::new(Expr::Literal(Value::String("foo".to_owned()))),
-    expr:  Box::new(Expr::Literal(Value::Num(1.0))),
+    expr:  Box::new(Expr::Literal(Value::Int(1))),
   }),]);
 }
 
@@ -223,7 +299,7 @@ fn parses_expr_stmt_continuation() {
     }),
     Stmt::Expr(Expr::Set(
       "foo".to_owned(),
-      Box::new(Expr::Literal(Value::Num(1.0)))
+      Box::new(Expr::Literal(Value::Int(1)))
     )),
   ]);
 }
@@ -232,7 +308,7 @@ fn parses_expr_stmt_continuation() {
 fn parses_explicit_cmd_stmt() {
   assert_eq!(parse("foo = 1"), vec![Stmt::Expr(Expr::Set(
     "foo".to_owned(),
-    Box::new(Expr::Literal(Value::Num(1.0)))
+    Box::new(Expr::Literal(Value::Int(1)))
   )),]);
   assert_eq!(parse("$ foo = 1"), vec![Stmt::Cmd(Cmd::Atom(vec![
     vec![Expr::Literal(Value::String("foo".to_owned()))],
@@ -256,7 +332,7 @@ fn parses_var_decl() {
   }]);
 
   assert_eq!(parse("let foo = 1"), vec![Stmt::Let {
-    init:   Some(Expr::Literal(Value::Num(1.0))),
+    init:   Some(Expr::Literal(Value::Int(1))),
     is_exp: false,
     name:   "foo".to_owned(),
   }]);
@@ -311,15 +387,15 @@ fn parses_cmd_with_interpolation() {
   assert_eq!(parse("a{1}b a{1} {1}b"), vec![Stmt::Cmd(Cmd::Atom(vec![
     vec![
       Expr::Literal(Value::String("a".to_owned())),
-      Expr::Literal(Value::Num(1.0)),
+      Expr::Literal(Value::Int(1)),
       Expr::Literal(Value::String("b".to_owned())),
     ],
     vec![
       Expr::Literal(Value::String("a".to_owned())),
-      Expr::Literal(Value::Num(1.0)),
+      Expr::Literal(Value::Int(1)),
     ],
     vec![
-      Expr::Literal(Value::Num(1.0)),
+      Expr::Literal(Value::Int(1)),
       Expr::Literal(Value::String("b".to_owned())),
     ],
   ])),]);
@@ -347,7 +423,7 @@ fn parses_return() {
   assert_eq!(parse("return"), vec![Stmt::Return(None),]);
 
   assert_eq!(parse("return 1"), vec![Stmt::Return(Some(Expr::Literal(
-    Value::Num(1.0)
+    Value::Int(1)
   ))),]);
 
   assert_eq!(parse("return\n1"), vec![
@@ -370,9 +446,38 @@ fn parses_break() {
 
 #[test]
 fn parses_import() {
-  assert_eq!(parse("import \"foobar\""), vec![Stmt::Import(
-    "foobar".to_owned()
-  ),]);
+  assert_eq!(parse("import \"foobar\""), vec![Stmt::Import {
+    path:  "foobar".to_owned(),
+    alias: None,
+  },]);
+}
+
+#[test]
+fn parses_import_as() {
+  assert_eq!(parse("import \"lib/git.koi\" as git\nas"), vec![
+    Stmt::Import {
+      path:  "lib/git.koi".to_owned(),
+      alias: Some("git".to_owned()),
+    },
+    Stmt::Cmd(Cmd::Atom(vec![vec![Expr::Literal(Value::String(
+      "as".to_owned()
+    ))]])),
+  ]);
+}
+
+#[test]
+fn parses_exp_fn() {
+  assert_eq!(parse("exp fn foo() {}"), vec![Stmt::Func {
+    func:   Func::User {
+      name:         Some("foo".to_owned()),
+      params:       vec![],
+      body:         Box::new(Stmt::Block(vec![])),
+      captured_env: None,
+    },
+    doc:    None,
+    target: Target::default(),
+    is_exp: true,
+  }]);
 }
 
 #[test]
@@ -457,24 +562,109 @@ fn parses_while() {
 
 #[test]
 fn parses_fn() {
-  assert_eq!(parse("fn foo \n( x , y , z ) \n {}"), vec![Stmt::Func(
-    Func::User {
+  assert_eq!(parse("fn foo \n( x , y , z ) \n {}"), vec![Stmt::Func {
+    func:   Func::User {
       name:         Some("foo".to_owned()),
       params:       vec!["x".to_owned(), "y".to_owned(), "z".to_owned()],
       body:         Box::new(Stmt::Block(vec![])),
       captured_env: None,
-    }
-  )]);
+    },
+    doc:    None,
+    target: Target::default(),
+    is_exp: false,
+  }]);
 }
 
 #[test]
 fn parses_fn_no_params() {
-  assert_eq!(parse("fn foo \n() \n {}"), vec![Stmt::Func(Func::User {
-    name:         Some("foo".to_owned()),
-    params:       vec![],
-    body:         Box::new(Stmt::Block(vec![])),
-    captured_env: None,
-  })]);
+  assert_eq!(parse("fn foo \n() \n {}"), vec![Stmt::Func {
+    func:   Func::User {
+      name:         Some("foo".to_owned()),
+      params:       vec![],
+      body:         Box::new(Stmt::Block(vec![])),
+      captured_env: None,
+    },
+    doc:    None,
+    target: Target::default(),
+    is_exp: false,
+  }]);
+}
+
+#[test]
+fn parses_fn_doc_comment() {
+  assert_eq!(
+    parse("# not a doc\n\n# Builds\n  # everything\nfn build() {}"),
+    vec![Stmt::Func {
+      func:   Func::User {
+        name:         Some("build".to_owned()),
+        params:       vec![],
+        body:         Box::new(Stmt::Block(vec![])),
+        captured_env: None,
+      },
+      doc:    Some("Builds\neverything".to_owned()),
+      target: Target::default(),
+      is_exp: false,
+    }]
+  );
+}
+
+#[test]
+fn parses_exported_fn_doc_comment() {
+  assert_eq!(parse("# Builds\nexp fn build() {}"), vec![Stmt::Func {
+    func:   Func::User {
+      name:         Some("build".to_owned()),
+      params:       vec![],
+      body:         Box::new(Stmt::Block(vec![])),
+      captured_env: None,
+    },
+    doc:    Some("Builds".to_owned()),
+    target: Target::default(),
+    is_exp: true,
+  }]);
+}
+
+#[test]
+fn parses_fn_deps() {
+  assert_eq!(parse("fn install(dir) : build,\n test {}"), vec![Stmt::Func {
+    func:   Func::User {
+      name:         Some("install".to_owned()),
+      params:       vec!["dir".to_owned()],
+      body:         Box::new(Stmt::Block(vec![])),
+      captured_env: None,
+    },
+    doc:    None,
+    target: Target {
+      deps:    vec!["build".to_owned(), "test".to_owned()],
+      inputs:  vec![],
+      outputs: vec![],
+    },
+    is_exp: false,
+  }]);
+}
+
+#[test]
+fn parses_fn_target() {
+  assert_eq!(
+    parse("fn main(): build, 'src/*.c', \n'lib/*.c' -> 'main' {}"),
+    vec![Stmt::Func {
+      func:   Func::User {
+        name:         Some("main".to_owned()),
+        params:       vec![],
+        body:         Box::new(Stmt::Block(vec![])),
+        captured_env: None,
+      },
+      doc:    None,
+      target: Target {
+        deps:    vec!["build".to_owned()],
+        inputs:  vec![
+          Expr::Literal(Value::String("src/*.c".to_owned())),
+          Expr::Literal(Value::String("lib/*.c".to_owned())),
+        ],
+        outputs: vec![Expr::Literal(Value::String("main".to_owned()))],
+      },
+      is_exp: false,
+    }]
+  );
 }
 
 #[test]
@@ -513,8 +703,8 @@ fn parses_block() {
 #[test]
 fn parses_range() {
   assert_eq!(parse_expression("0..5"), Expr::Range {
-    l:         Box::new(Expr::Literal(Value::Num(0.0))),
-    r:         Box::new(Expr::Literal(Value::Num(5.0))),
+    l:         Box::new(Expr::Literal(Value::Int(0))),
+    r:         Box::new(Expr::Literal(Value::Int(5))),
     inclusive: false,
   });
 }
@@ -522,8 +712,8 @@ fn parses_range() {
 #[test]
 fn parses_range_inclusive() {
   assert_eq!(parse_expression("0..=5"), Expr::Range {
-    l:         Box::new(Expr::Literal(Value::Num(0.0))),
-    r:         Box::new(Expr::Literal(Value::Num(5.0))),
+    l:         Box::new(Expr::Literal(Value::Int(0))),
+    r:         Box::new(Expr::Literal(Value::Int(5))),
     inclusive: true,
   });
 }
@@ -542,16 +732,16 @@ fn parses_range_complex_exprs() {
       Box::new(Expr::Call {
         func: Box::new(Expr::GetField {
           base:  Box::new(Expr::Vec(vec![
-            Expr::Literal(Value::Num(1.0)),
-            Expr::Literal(Value::Num(2.0)),
-            Expr::Literal(Value::Num(3.0)),
+            Expr::Literal(Value::Int(1)),
+            Expr::Literal(Value::Int(2)),
+            Expr::Literal(Value::Int(3)),
           ])),
           index: Box::new(Expr::Literal(Value::String("len".to_string()))),
         }),
         args: vec![],
       }),
       BinaryOp::Sum,
-      Box::new(Expr::Literal(Value::Num(1.0))),
+      Box::new(Expr::Literal(Value::Int(1))),
     )),
     inclusive: true,
   });
@@ -562,19 +752,19 @@ fn parses_vec_literal() {
   assert_eq!(
     parse_expression("[\n1,\n2\n3,]"),
     Expr::Vec(vec![
-      Expr::Literal(Value::Num(1.0)),
-      Expr::Literal(Value::Num(2.0)),
-      Expr::Literal(Value::Num(3.0)),
+      Expr::Literal(Value::Int(1)),
+      Expr::Literal(Value::Int(2)),
+      Expr::Literal(Value::Int(3)),
     ])
   );
 }
 
 #[test]
 fn parses_dict_literal() {
-  let mut map = HashMap::new();
-  map.insert("a".to_string(), Expr::Literal(Value::Num(1.0)));
-  map.insert("b".to_string(), Expr::Literal(Value::Num(2.0)));
-  map.insert("c".to_string(), Expr::Literal(Value::Num(3.0)));
+  let mut map = IndexMap::new();
+  map.insert("a".to_string(), Expr::Literal(Value::Int(1)));
+  map.insert("b".to_string(), Expr::Literal(Value::Int(2)));
+  map.insert("c".to_string(), Expr::Literal(Value::Int(3)));
   assert_eq!(parse_expression("{\na : 1,\nb : 2\nc : 3,}"), Expr::Dict(map));
 }
 
@@ -583,9 +773,9 @@ fn parses_call() {
   assert_eq!(parse_expression("f(\n1,\n2\n3,)"), Expr::Call {
     func: Box::new(Expr::Get("f".to_string())),
     args: vec![
-      Expr::Literal(Value::Num(1.0)),
-      Expr::Literal(Value::Num(2.0)),
-      Expr::Literal(Value::Num(3.0)),
+      Expr::Literal(Value::Int(1)),
+      Expr::Literal(Value::Int(2)),
+      Expr::Literal(Value::Int(3)),
     ],
   });
 }
@@ -0,0 +1,321 @@
+use {
+  crate::{
+    interp::{method_names, panic_message, Interpreter, Value},
+    lexer::new as new_lexer,
+    parser::Parser,
+    token::{Token, TokenKind},
+  },
+  rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::FileHistory,
+    validate::{ValidationContext, ValidationResult, Validator},
+    Context, Editor, Helper,
+  },
+  std::{
+    cell::{Cell, RefCell},
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+  },
+};
+
+#[cfg(test)] mod test;
+
+thread_local! {
+  // Set while panics are expected and shouldn't be reported
+  static QUIET: Cell<bool> = Cell::new(false);
+}
+
+struct KoiHelper {
+  interpreter: Rc<RefCell<Interpreter>>,
+}
+
+pub fn run(interpreter: Interpreter) {
+  let interpreter = Rc::new(RefCell::new(interpreter));
+
+  let mut editor = Editor::<KoiHelper, FileHistory>::new()
+    .expect("couldn't start the interactive prompt");
+  editor.set_helper(Some(KoiHelper { interpreter: Rc::clone(&interpreter) }));
+
+  let history = dirs::home_dir().map(|home| home.join(".koi_history"));
+  if let Some(history) = &history {
+    // The history file doesn't exist on the first run
+    let _ = editor.load_history(history);
+  }
+
+  // Errors are printed without the thread name and location, as they refer to
+  // what the user typed
+  panic::set_hook(Box::new(|info| {
+    if QUIET.with(Cell::get) {
+      return;
+    }
+
+    let msg = if let Some(msg) = info.payload().downcast_ref::<&str>() {
+      msg.to_string()
+    } else if let Some(msg) = info.payload().downcast_ref::<String>() {
+      msg.clone()
+    } else {
+      "unknown error".to_string()
+    };
+
+    eprintln!("error: {}", msg);
+  }));
+
+  loop {
+    let source = match editor.readline("koi> ") {
+      Ok(source) => source,
+      Err(ReadlineError::Interrupted) => continue,
+      Err(ReadlineError::Eof) => break,
+      Err(err) => panic!("couldn't read line: {}", err),
+    };
+
+    if source.trim().is_empty() {
+      continue;
+    }
+
+    let _ = editor.add_history_entry(source.as_str());
+
+    match eval_line(&mut interpreter.borrow_mut(), source) {
+      Some(Value::Nil) | None => (),
+      Some(val) => println!("{}", val.to_string_quoted()),
+    }
+  }
+
+  let _ = panic::take_hook();
+
+  if let Some(history) = &history {
+    let _ = editor.save_history(history);
+  }
+}
+
+// Runs a line that may fail. A failed line is left half run, so the
+// interpreter is put back in the scope it was in before it
+fn eval_line(interpreter: &mut Interpreter, source: String) -> Option<Value> {
+  let state = interpreter.save_state();
+
+  match panic::catch_unwind(AssertUnwindSafe(|| {
+    eval_input(interpreter, source)
+  })) {
+    Ok(val) => Some(val),
+    Err(_) => {
+      interpreter.restore_state(state);
+      None
+    }
+  }
+}
+
+// Runs a chunk of input, returning the value of expressions so that it can be
+// shown to the user
+fn eval_input(interpreter: &mut Interpreter, source: String) -> Value {
+  let tokens = new_lexer(source.clone())
+    .filter(|t| t.kind != TokenKind::Space)
+    .collect::<Vec<Token>>();
+
+  let mut parser = Parser::new(new_lexer(source));
+
+  if is_expr_input(interpreter, &tokens) {
+    let expr = parser.parse_single_expr();
+    interpreter.eval_expr(expr)
+  } else {
+    interpreter.run(parser.parse());
+    Value::Nil
+  }
+}
+
+// Lines are commands or statements as in scripts, except for the ones that
+// couldn't be anything but an expression and the ones starting with a defined
+// variable, as in `x`, `x.keys()` or `x * 2`
+fn is_expr_input(interpreter: &Interpreter, tokens: &[Token]) -> bool {
+  use TokenKind::*;
+
+  match tokens.first().map(|t| &t.kind) {
+    Some(Int(_))
+    | Some(Num(_))
+    | Some(String { .. })
+    | Some(True)
+    | Some(False)
+    | Some(Nil)
+    | Some(LeftParen)
+    | Some(LeftBracket)
+    | Some(Minus)
+    | Some(Bang)
+    | Some(DollarLeftParen) => true,
+    Some(Identifier(name)) if interpreter.has_var(name) => {
+      let rest = &tokens[1 + lookup_len(&tokens[1..])..];
+
+      // Operators that can also be part of a command, like `|`, are left out
+      matches!(
+        rest.first().map(|t| &t.kind),
+        None
+          | Some(Newline)
+          | Some(Plus)
+          | Some(Minus)
+          | Some(Star)
+          | Some(Slash)
+          | Some(SlashSlash)
+          | Some(Perc)
+          | Some(Caret)
+          | Some(EqualEqual)
+          | Some(BangEqual)
+          | Some(LessEqual)
+          | Some(GreatEqual)
+          | Some(DotDot)
+      )
+    }
+    _ => false,
+  }
+}
+
+// Length of the rest of a lookup or call chain like `.y['z'](1)`
+fn lookup_len(tokens: &[Token]) -> usize {
+  let mut depth = 0;
+  let mut len = 0;
+
+  for (i, token) in tokens.iter().enumerate() {
+    match &token.kind {
+      TokenKind::LeftBracket | TokenKind::LeftParen => depth += 1,
+      TokenKind::RightBracket | TokenKind::RightParen if depth > 0 =>
+        depth -= 1,
+      TokenKind::Dot if depth == 0 => continue,
+      TokenKind::Identifier(_)
+        if depth == 0
+          && i > 0
+          && matches!(tokens[i - 1].kind, TokenKind::Dot) =>
+        (),
+      _ if depth > 0 => continue,
+      _ => break,
+    }
+
+    if depth == 0 {
+      len = i + 1;
+    }
+  }
+
+  len
+}
+
+// Input is incomplete while blocks, parentheses, brackets or strings are left
+// open
+fn is_incomplete(source: &str) -> bool {
+  QUIET.with(|quiet| quiet.set(true));
+  let tokens = panic::catch_unwind(|| {
+    new_lexer(source.to_string()).collect::<Vec<Token>>()
+  });
+  QUIET.with(|quiet| quiet.set(false));
+
+  let tokens = match tokens {
+    Ok(tokens) => tokens,
+    Err(err) => {
+      let msg = panic_message(err);
+
+      return msg.starts_with("unterminated string")
+        || msg.starts_with("expected closing brace");
+    }
+  };
+
+  let depth = tokens.iter().fold(0, |depth, t| match t.kind {
+    TokenKind::LeftBrace
+    | TokenKind::LeftParen
+    | TokenKind::LeftBracket
+    | TokenKind::DollarLeftParen => depth + 1,
+    TokenKind::RightBrace | TokenKind::RightParen | TokenKind::RightBracket =>
+      depth - 1,
+    _ => depth,
+  });
+
+  depth > 0
+}
+
+// Candidates for the word ending at `pos`, that is variables or, after a dot,
+// methods and dict keys
+fn complete(
+  interpreter: &Interpreter,
+  line: &str,
+  pos: usize,
+) -> (usize, Vec<String>) {
+  let start = line[..pos]
+    .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+    .map_or(0, |i| i + 1);
+  let word = &line[start..pos];
+
+  let (start, prefix, mut names) = match word.rfind('.') {
+    None => (start, word, interpreter.var_names()),
+    Some(dot) => {
+      let base = &word[..dot];
+
+      if !base.starts_with(|c: char| c == '_' || c.is_alphabetic()) {
+        return (pos, Vec::new());
+      }
+
+      let mut path = base.split('.');
+      let mut val = interpreter.get_var(path.next().unwrap());
+      for key in path {
+        val = match val {
+          Value::Dict(dict) =>
+            dict.borrow().get(key).cloned().unwrap_or(Value::Nil),
+          _ => Value::Nil,
+        };
+      }
+
+      let mut names =
+        method_names(&val).into_iter().map(String::from).collect::<Vec<_>>();
+      if let Value::Dict(dict) = &val {
+        names.extend(dict.borrow().keys().cloned());
+      }
+
+      (start + dot + 1, &word[dot + 1..], names)
+    }
+  };
+
+  names.retain(|name| name.starts_with(prefix));
+  names.sort();
+  names.dedup();
+
+  (start, names)
+}
+
+impl Completer for KoiHelper {
+  type Candidate = Pair;
+
+  fn complete(
+    &self,
+    line: &str,
+    pos: usize,
+    _ctx: &Context<'_>,
+  ) -> rustyline::Result<(usize, Vec<Pair>)> {
+    // The interpreter is borrowed while running, but the prompt only shows up
+    // once it's done
+    let (start, names) = complete(&self.interpreter.borrow(), line, pos);
+
+    Ok((
+      start,
+      names
+        .into_iter()
+        .map(|name| Pair { display: name.clone(), replacement: name })
+        .collect(),
+    ))
+  }
+}
+
+impl Validator for KoiHelper {
+  fn validate(
+    &self,
+    ctx: &mut ValidationContext,
+  ) -> rustyline::Result<ValidationResult> {
+    Ok(if is_incomplete(ctx.input()) {
+      ValidationResult::Incomplete
+    } else {
+      ValidationResult::Valid(None)
+    })
+  }
+}
+
+impl Hinter for KoiHelper {
+  type Hint = String;
+}
+
+impl Highlighter for KoiHelper {}
+
+impl Helper for KoiHelper {}
@@ -0,0 +1,111 @@
+use {
+  super::*,
+  crate::{lexer::new as new_lexer, token::TokenKind},
+};
+
+fn tokens(source: &str) -> Vec<Token> {
+  new_lexer(source.to_string()).filter(|t| t.kind != TokenKind::Space).collect()
+}
+
+#[test]
+fn detects_incomplete_input() {
+  assert!(is_incomplete("fn foo() {"));
+  assert!(is_incomplete("print([1,\n2"));
+  assert!(is_incomplete("let s = 'unterminated"));
+  assert!(is_incomplete("let s = $(ls"));
+  assert!(!is_incomplete("fn foo() {\n  print(1)\n}"));
+  assert!(!is_incomplete("echo 'a{(1)}b'"));
+}
+
+#[test]
+fn detects_expr_input() {
+  let mut interpreter = Interpreter::new();
+  interpreter.run(Parser::new(new_lexer("let x = {y: [1]}".into())).parse());
+
+  for (source, want) in &[
+    ("1 + 2", true),
+    ("'koi'.upper()", true),
+    ("(x.y)", true),
+    ("x", true),
+    ("x.y[0]", true),
+    ("x.keys()", true),
+    ("x.y[0] * 2", true),
+    ("print(x)", true),
+    ("x y", false),
+    ("x = 1", false),
+    ("ls -la", false),
+    ("env | grep PATH", false),
+    ("let y = 1", false),
+  ] {
+    assert_eq!(
+      is_expr_input(&interpreter, &tokens(source)),
+      *want,
+      "{}",
+      source
+    );
+  }
+}
+
+#[test]
+fn evaluates_input_in_persistent_state() {
+  let mut interpreter = Interpreter::new();
+
+  assert_eq!(eval_input(&mut interpreter, "let x = 20".into()), Value::Nil);
+  assert_eq!(eval_input(&mut interpreter, "x = x + 1".into()), Value::Nil);
+  assert_eq!(eval_input(&mut interpreter, "x * 2".into()), Value::Int(42));
+  assert_eq!(
+    eval_input(&mut interpreter, "fn f(a) {\n  return a + x\n}".into()),
+    Value::Nil
+  );
+  assert_eq!(eval_input(&mut interpreter, "[f(1)]".into()).to_string(), "[22]");
+}
+
+#[test]
+fn recovers_from_failed_input() {
+  let mut interpreter = Interpreter::new();
+
+  assert_eq!(
+    eval_line(&mut interpreter, "fn f() {\n  let y = 1\n  exit('x')\n}".into()),
+    Some(Value::Nil)
+  );
+  assert_eq!(eval_line(&mut interpreter, "f()".into()), None);
+  assert_eq!(
+    eval_line(&mut interpreter, "withEnv({KOI_X: 1}) {\n  f()\n}".into()),
+    None
+  );
+  assert_eq!(eval_line(&mut interpreter, "let x = 2".into()), Some(Value::Nil));
+  assert_eq!(
+    eval_line(&mut interpreter, "[x, y]".into()).unwrap().to_string(),
+    "[2, nil]"
+  );
+  assert_eq!(
+    eval_line(&mut interpreter, "env.get('KOI_X')".into()),
+    Some(Value::Nil)
+  );
+}
+
+#[test]
+fn completes_names() {
+  let mut interpreter = Interpreter::new();
+  interpreter.run(
+    Parser::new(new_lexer("let koi_name = 'x' let koi_dict = {key: 1}".into()))
+      .parse(),
+  );
+
+  assert_eq!(
+    complete(&interpreter, "print(koi_", 10),
+    (6, vec!["koi_dict".to_string(), "koi_name".to_string()])
+  );
+  assert_eq!(
+    complete(&interpreter, "koi_name.up", 11),
+    (9, vec!["upper".to_string()])
+  );
+  assert_eq!(
+    complete(&interpreter, "koi_dict.k", 10),
+    (9, vec!["key".to_string(), "keys".to_string()])
+  );
+  assert_eq!(
+    complete(&interpreter, "math.sq", 7),
+    (5, vec!["sqrt".to_string()])
+  );
+}
@@ -0,0 +1,128 @@
+use {
+  crate::{
+    ast::{Expr, Target},
+    interp::{Interpreter, Value},
+  },
+  serde_json::{Map as JSONMap, Value as JSONValue},
+  std::{fs, path::PathBuf, time::SystemTime},
+};
+
+pub const CACHE_PATH: &str = ".koi-cache";
+
+// Hashes of the inputs of tasks as of their last run, so that files touched
+// without being changed don't make tasks run again
+pub struct Cache {
+  path:   PathBuf,
+  hashes: JSONMap<String, JSONValue>,
+}
+
+impl Cache {
+  pub fn load(path: PathBuf) -> Cache {
+    // A missing or broken cache only means that tasks may run again
+    let hashes = fs::read_to_string(&path)
+      .ok()
+      .and_then(|json| serde_json::from_str(&json).ok())
+      .unwrap_or_default();
+
+    Cache { path, hashes }
+  }
+
+  pub fn get(&self, key: &str) -> Option<&str> {
+    self.hashes.get(key).and_then(JSONValue::as_str)
+  }
+
+  pub fn set(&mut self, key: String, hash: String) {
+    self.hashes.insert(key, JSONValue::String(hash));
+
+    let json = serde_json::to_string_pretty(&self.hashes).unwrap();
+    if let Err(err) = fs::write(&self.path, json) {
+      panic!("couldn't write '{}': {}", self.path.display(), err);
+    }
+  }
+}
+
+// The files read and written by a task, with input globs expanded
+pub struct Files {
+  inputs:  Vec<PathBuf>,
+  outputs: Vec<PathBuf>,
+}
+
+impl Files {
+  pub fn resolve(interpreter: &mut Interpreter, target: &Target) -> Files {
+    let patterns = target
+      .inputs
+      .iter()
+      .flat_map(|expr| paths(interpreter.eval_expr(expr.clone())))
+      .collect::<Vec<_>>();
+
+    let inputs = patterns
+      .into_iter()
+      .flat_map(|pattern| {
+        paths(interpreter.eval_expr(Expr::Call {
+          func: Box::new(Expr::Get("glob".to_string())),
+          args: vec![Expr::Literal(Value::String(pattern))],
+        }))
+      })
+      .map(PathBuf::from)
+      .collect();
+
+    let outputs = target
+      .outputs
+      .iter()
+      .flat_map(|expr| paths(interpreter.eval_expr(expr.clone())))
+      .map(PathBuf::from)
+      .collect();
+
+    Files { inputs, outputs }
+  }
+
+  // Outputs are fresh when they exist and are newer than all the inputs, or
+  // when the inputs haven't changed since the last run
+  pub fn is_fresh(&self, cache: &Cache, key: &str) -> bool {
+    let oldest_output = match self
+      .outputs
+      .iter()
+      .map(mtime)
+      .collect::<Option<Vec<_>>>()
+      .and_then(|mtimes| mtimes.into_iter().min())
+    {
+      Some(mtime) => mtime,
+      None => return false,
+    };
+
+    self
+      .inputs
+      .iter()
+      .all(|input| mtime(input).map_or(false, |mtime| mtime <= oldest_output))
+      || cache.get(key) == Some(self.hash().as_str())
+  }
+
+  // FNV-1a of the paths and contents of the inputs
+  pub fn hash(&self) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    for input in &self.inputs {
+      let path = input.to_string_lossy().into_owned().into_bytes();
+      let content = fs::read(input).unwrap_or_default();
+
+      for byte in path.into_iter().chain([0]).chain(content).chain([0]) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+      }
+    }
+
+    format!("{:016x}", hash)
+  }
+}
+
+fn mtime(path: &PathBuf) -> Option<SystemTime> {
+  fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn paths(val: Value) -> Vec<String> {
+  match val {
+    Value::String(path) => vec![path],
+    Value::Vec(vec) => vec.borrow().iter().cloned().flat_map(paths).collect(),
+    _ => panic!("expected task inputs and outputs to be strings"),
+  }
+}
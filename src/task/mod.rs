@@ -0,0 +1,276 @@
+pub use pool::{run_parallel, Script};
+use {
+  crate::{
+    ast::{Expr, Prog, Stmt, Target},
+    interp::{Func, Interpreter, Value},
+  },
+  cache::{Cache, Files, CACHE_PATH},
+  indexmap::IndexMap,
+  itertools::Itertools,
+  std::{iter, path::PathBuf},
+};
+
+mod cache;
+mod pool;
+
+#[cfg(test)] mod test;
+
+// A task function along with the arguments to call it with
+#[derive(Debug, PartialEq)]
+pub struct Task {
+  pub name: String,
+  pub args: Vec<Value>,
+}
+
+impl Task {
+  // Identifies the task in the cache
+  fn key(&self) -> String {
+    iter::once(self.name.clone())
+      .chain(self.args.iter().map(Value::to_string))
+      .join(" ")
+  }
+}
+
+pub type Targets = IndexMap<String, Target>;
+
+// Runs the tasks named on the command line in order, after making sure that
+// all of them exist and got the right arguments. Tasks with outputs are skipped
+// while those are fresh, and when more than one task is going to run each one
+// is announced on stderr
+pub fn run(interpreter: &mut Interpreter, targets: &Targets, words: &[String]) {
+  let tasks = plan(interpreter, targets, words);
+  let announce = tasks.len() > 1;
+  let mut cache = Cache::load(PathBuf::from(CACHE_PATH));
+
+  for task in tasks {
+    let files = files(interpreter, targets, &task);
+    if is_fresh(&files, &cache, &task) {
+      continue;
+    }
+
+    if announce {
+      eprintln!("running task '{}'", task.name);
+    }
+
+    let key = task.key();
+    interpreter.eval_expr(Expr::Call {
+      func: Box::new(Expr::Get(task.name)),
+      args: task.args.into_iter().map(Expr::Literal).collect(),
+    });
+
+    if let Some(files) = files {
+      cache.set(key, files.hash());
+    }
+  }
+}
+
+// The files of a task that declares outputs
+fn files(
+  interpreter: &mut Interpreter,
+  targets: &Targets,
+  task: &Task,
+) -> Option<Files> {
+  match targets.get(&task.name) {
+    Some(target) if !target.outputs.is_empty() =>
+      Some(Files::resolve(interpreter, target)),
+    _ => None,
+  }
+}
+
+fn is_fresh(files: &Option<Files>, cache: &Cache, task: &Task) -> bool {
+  let is_fresh =
+    files.as_ref().map_or(false, |files| files.is_fresh(cache, &task.key()));
+  if is_fresh {
+    eprintln!("task '{}' is up to date", task.name);
+  }
+  is_fresh
+}
+
+// Targets of the functions declared at the top level of a program
+pub fn targets(prog: &Prog) -> Targets {
+  prog
+    .iter()
+    .filter_map(|stmt| match stmt {
+      Stmt::Func {
+        func: Func::User { name: Some(name), .. }, target, ..
+      } => Some((name.clone(), target.clone())),
+      _ => None,
+    })
+    .collect()
+}
+
+// Orders the tasks named on the command line so that each one comes after the
+// tasks it depends on. A task never runs twice with the same args
+pub fn plan(
+  interpreter: &Interpreter,
+  targets: &Targets,
+  words: &[String],
+) -> Vec<Task> {
+  let mut plan = Vec::new();
+
+  for task in bind(interpreter, words) {
+    plan_deps(interpreter, targets, &task.name, &mut Vec::new(), &mut plan);
+
+    if !plan.contains(&task) {
+      plan.push(task);
+    }
+  }
+
+  plan
+}
+
+fn plan_deps<'a>(
+  interpreter: &Interpreter,
+  targets: &'a Targets,
+  name: &'a str,
+  path: &mut Vec<&'a str>,
+  plan: &mut Vec<Task>,
+) {
+  path.push(name);
+
+  for dep in targets.get(name).into_iter().flat_map(|t| &t.deps) {
+    if path.contains(&dep.as_str()) {
+      panic!(
+        "dependency cycle between tasks: {} -> {}",
+        path.iter().skip_while(|&name| name != dep).join(" -> "),
+        dep
+      );
+    }
+
+    if !is_task(interpreter, dep) {
+      panic!("task '{}' depends on unknown task '{}'", name, dep);
+    }
+
+    if !params(interpreter, dep).is_empty() {
+      panic!("task '{}' depends on '{}', which takes args", name, dep);
+    }
+
+    plan_deps(interpreter, targets, dep, path, plan);
+
+    let task = Task { name: dep.clone(), args: Vec::new() };
+    if !plan.contains(&task) {
+      plan.push(task);
+    }
+  }
+
+  path.pop();
+}
+
+// Splits words like `deploy staging --force build` into task calls. Positional
+// words fill in parameters in order and `--name` or `--name=value` set them by
+// name, while a word coming after all the parameters are set starts a new task.
+// Values are strings like the words they come from, except for a bare `--name`,
+// which is `true`
+pub fn bind(interpreter: &Interpreter, words: &[String]) -> Vec<Task> {
+  let mut tasks = Vec::new();
+  let mut words = words.iter().peekable();
+
+  while let Some(name) = words.next() {
+    // Anything that isn't a task after one is done is taken as an extra arg
+    if let Some(prev) = tasks.last() {
+      if !is_task(interpreter, name) {
+        let Task { name: prev, args } = prev;
+        panic!(
+          "too many args for task '{}', expected {} but got '{}'",
+          prev,
+          args.len(),
+          name
+        );
+      }
+    }
+
+    let params = params(interpreter, name);
+    let mut bound: IndexMap<&str, Value> = IndexMap::new();
+
+    while bound.len() < params.len() {
+      let word = match words.peek() {
+        Some(word) => word,
+        None => break,
+      };
+
+      if let Some(flag) = word.strip_prefix("--") {
+        let (param, val) = match flag.find('=') {
+          Some(i) => (&flag[..i], Value::String(flag[i + 1..].to_string())),
+          None => (flag, Value::Bool(true)),
+        };
+
+        if !params.iter().any(|p| p == param) {
+          panic!("task '{}' has no parameter named '{}'", name, param);
+        }
+
+        if bound.insert(param, val).is_some() {
+          panic!("parameter '{}' of task '{}' set twice", param, name);
+        }
+      } else {
+        let param =
+          params.iter().find(|p| !bound.contains_key(p.as_str())).unwrap();
+        bound.insert(param, Value::String(word.to_string()));
+      }
+
+      words.next();
+    }
+
+    if bound.len() < params.len() {
+      panic!(
+        "too few args for task '{}', expected {} but got {}",
+        name,
+        params.len(),
+        bound.len()
+      );
+    }
+
+    tasks.push(Task {
+      name: name.clone(),
+      args: params.iter().map(|p| bound[p.as_str()].clone()).collect(),
+    });
+  }
+
+  tasks
+}
+
+fn is_task(interpreter: &Interpreter, name: &str) -> bool {
+  interpreter.has_var(name)
+    && matches!(interpreter.get_var(name), Value::Func(Func::User { .. }))
+}
+
+fn params(interpreter: &Interpreter, name: &str) -> Vec<String> {
+  match interpreter.has_var(name).then(|| interpreter.get_var(name)) {
+    Some(Value::Func(Func::User { params, .. })) => params,
+    _ => panic!("no task named '{}'", name),
+  }
+}
+
+// Describes the functions declared at the top level of a program, one per line
+// and followed by their doc comment if any
+pub fn list(prog: &Prog) -> String {
+  let tasks = prog
+    .iter()
+    .filter_map(|stmt| match stmt {
+      Stmt::Func {
+        func: Func::User { name: Some(name), params, .. },
+        doc,
+        ..
+      } => Some((
+        iter::once(name).chain(params).join(" "),
+        doc.as_deref().unwrap_or(""),
+      )),
+      _ => None,
+    })
+    .collect_vec();
+
+  let width = tasks.iter().map(|(usage, _)| usage.len()).max().unwrap_or(0);
+
+  tasks
+    .iter()
+    .map(|(usage, doc)| {
+      let doc = doc.lines().join(&format!("\n{:1$}", "", width + 3));
+
+      if doc.is_empty() {
+        usage.to_string()
+      } else {
+        format!("{:2$} # {}", usage, doc, width)
+      }
+    })
+    .map(|line| line + "\n")
+    .collect()
+}
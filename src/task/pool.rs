@@ -0,0 +1,256 @@
+use {
+  super::{
+    cache::{Cache, CACHE_PATH},
+    files, is_fresh, plan, Targets,
+  },
+  crate::{
+    ast::{Expr, Prog},
+    interp::{panic_message, Interpreter, Snapshot, Value},
+    lexer::new as new_lexer,
+    parser::Parser,
+  },
+  itertools::Itertools,
+  serde_json::Value as JSONValue,
+  std::{
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{
+      mpsc::{self, Receiver, Sender},
+      Arc, Mutex, Once,
+    },
+    thread,
+  },
+};
+
+// What's needed to set up an interpreter for each task, as interpreters can't
+// be shared between threads. The state the script was left in is copied over
+// from a snapshot, so the top level isn't run again
+pub struct Script {
+  pub source:      String,
+  pub import_root: Option<PathBuf>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+  Waiting,
+  Running,
+  Done,
+  Failed,
+}
+
+struct Job {
+  index: usize,
+  name:  String,
+  args:  Vec<JSONValue>,
+}
+
+struct Outcome {
+  index:  usize,
+  output: String,
+  errors: String,
+  res:    Result<(), String>,
+}
+
+const WORKER_NAME: &str = "koi-task";
+static SILENCE_WORKERS: Once = Once::new();
+
+// Runs tasks on `jobs` threads as soon as the tasks they depend on are done.
+// Each task gets its own interpreter, and what it prints, errors included, is
+// buffered and then shown with the name of the task in front of every line.
+// After a task fails no other task is started, unless `keep_going` is set
+pub fn run_parallel(
+  interpreter: &mut Interpreter,
+  targets: &Targets,
+  words: &[String],
+  script: Script,
+  jobs: usize,
+  keep_going: bool,
+) {
+  let tasks = plan(interpreter, targets, words);
+  let announce = tasks.len() > 1;
+  let mut cache = Cache::load(PathBuf::from(CACHE_PATH));
+
+  // Dependencies always come earlier in the plan
+  let prereqs = tasks
+    .iter()
+    .map(|task| {
+      targets.get(&task.name).map_or(Vec::new(), |target| {
+        target
+          .deps
+          .iter()
+          .map(|dep| tasks.iter().position(|other| other.name == *dep).unwrap())
+          .collect_vec()
+      })
+    })
+    .collect_vec();
+
+  let mut states = vec![State::Waiting; tasks.len()];
+  let mut task_files = tasks.iter().map(|_| None).collect_vec();
+
+  let script = Arc::new(script);
+  let snapshot = Arc::new(interpreter.snapshot());
+  let (job_tx, job_rx) = mpsc::channel();
+  let job_rx = Arc::new(Mutex::new(job_rx));
+  let (outcome_tx, outcome_rx) = mpsc::channel();
+
+  // Failed tasks are reported along with their name once they're done, so the
+  // panic messages of the workers would only get in the way
+  SILENCE_WORKERS.call_once(|| {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+      if thread::current().name() != Some(WORKER_NAME) {
+        hook(info);
+      }
+    }));
+  });
+
+  let workers = (0..jobs)
+    .map(|_| {
+      let script = Arc::clone(&script);
+      let snapshot = Arc::clone(&snapshot);
+      let job_rx = Arc::clone(&job_rx);
+      let outcome_tx = outcome_tx.clone();
+      thread::Builder::new()
+        .name(WORKER_NAME.to_string())
+        .spawn(move || work(&script, &snapshot, &job_rx, &outcome_tx))
+        .unwrap()
+    })
+    .collect_vec();
+
+  let mut running = 0;
+  let mut failed = Vec::new();
+
+  loop {
+    for (index, task) in tasks.iter().enumerate() {
+      if running == jobs || (!failed.is_empty() && !keep_going) {
+        break;
+      }
+
+      if states[index] != State::Waiting
+        || !prereqs[index].iter().all(|&i| states[i] == State::Done)
+      {
+        continue;
+      }
+
+      task_files[index] = files(interpreter, targets, task);
+      if is_fresh(&task_files[index], &cache, task) {
+        states[index] = State::Done;
+        continue;
+      }
+
+      if announce {
+        eprintln!("running task '{}'", task.name);
+      }
+
+      states[index] = State::Running;
+      running += 1;
+      job_tx
+        .send(Job {
+          index,
+          name: task.name.clone(),
+          args: task.args.iter().cloned().map(JSONValue::from).collect(),
+        })
+        .unwrap();
+    }
+
+    if running == 0 {
+      break;
+    }
+
+    let Outcome { index, output, errors, res } = outcome_rx.recv().unwrap();
+    let task = &tasks[index];
+    running -= 1;
+
+    for line in output.lines() {
+      println!("[{}] {}", task.name, line);
+    }
+    for line in errors.lines() {
+      eprintln!("[{}] {}", task.name, line);
+    }
+
+    match res {
+      Ok(()) => {
+        states[index] = State::Done;
+        if let Some(files) = &task_files[index] {
+          cache.set(task.key(), files.hash());
+        }
+      }
+      Err(msg) => {
+        eprintln!("task '{}' failed: {}", task.name, msg);
+        states[index] = State::Failed;
+        failed.push(task.name.as_str());
+      }
+    }
+  }
+
+  drop(job_tx);
+  for worker in workers {
+    worker.join().unwrap();
+  }
+
+  if !failed.is_empty() {
+    let skipped = tasks
+      .iter()
+      .zip(&states)
+      .filter(|(_, state)| **state == State::Waiting)
+      .map(|(task, _)| task.name.as_str())
+      .collect_vec();
+    if !skipped.is_empty() {
+      eprintln!("tasks not run: {}", skipped.join(", "));
+    }
+
+    panic!("some tasks failed: {}", failed.join(", "));
+  }
+}
+
+fn work(
+  script: &Script,
+  snapshot: &Arc<Snapshot>,
+  jobs: &Mutex<Receiver<Job>>,
+  outcomes: &Sender<Outcome>,
+) {
+  let prog = Parser::new(new_lexer(script.source.clone())).parse();
+
+  loop {
+    let Job { index, name, args } = match jobs.lock().unwrap().recv() {
+      Ok(job) => job,
+      Err(_) => break,
+    };
+
+    let mut interpreter = Interpreter::new();
+    if let Some(import_root) = &script.import_root {
+      interpreter.set_import_root(import_root.clone());
+    }
+    interpreter.do_collect();
+    interpreter.do_collect_err();
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+      run_job(&mut interpreter, prog.clone(), snapshot, name, args)
+    }))
+    .map_err(panic_message);
+
+    outcomes
+      .send(Outcome {
+        index,
+        output: interpreter.take_collected(),
+        errors: interpreter.take_collected_err(),
+        res,
+      })
+      .unwrap();
+  }
+}
+
+fn run_job(
+  interpreter: &mut Interpreter,
+  prog: Prog,
+  snapshot: &Arc<Snapshot>,
+  name: String,
+  args: Vec<JSONValue>,
+) {
+  interpreter.restore_snapshot(prog, Arc::clone(snapshot));
+
+  interpreter.eval_expr(Expr::Call {
+    func: Box::new(Expr::Get(name)),
+    args: args.into_iter().map(|arg| Expr::Literal(Value::from(arg))).collect(),
+  });
+}
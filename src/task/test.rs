@@ -0,0 +1,250 @@
+use {
+  super::*,
+  crate::{lexer::new as new_lexer, parser::Parser},
+  std::{
+    env, fs, panic,
+    path::Path,
+    process,
+    time::{Duration, SystemTime},
+  },
+};
+
+fn interpreter() -> Interpreter {
+  let mut interpreter = Interpreter::new();
+  interpreter.run(
+    Parser::new(new_lexer(
+      "fn build() {}\nfn test() {}\nfn deploy(env, force) {}\nlet x = 1".into(),
+    ))
+    .parse(),
+  );
+  interpreter
+}
+
+fn words(words: &str) -> Vec<String> {
+  words.split(' ').map(String::from).collect()
+}
+
+fn task(name: &str, args: Vec<Value>) -> Task {
+  Task { name: name.to_string(), args }
+}
+
+#[test]
+fn binds_multiple_tasks() {
+  assert_eq!(bind(&interpreter(), &words("build test build")), vec![
+    task("build", vec![]),
+    task("test", vec![]),
+    task("build", vec![])
+  ]);
+}
+
+#[test]
+fn binds_positional_and_flag_args() {
+  let interpreter = interpreter();
+
+  assert_eq!(bind(&interpreter, &words("deploy staging --force test")), vec![
+    task("deploy", vec![Value::String("staging".into()), Value::Bool(true)]),
+    task("test", vec![]),
+  ]);
+  assert_eq!(bind(&interpreter, &words("deploy --force=1 prod")), vec![task(
+    "deploy",
+    vec![Value::String("prod".into()), Value::String("1".into())]
+  )]);
+  assert_eq!(
+    bind(&interpreter, &words("deploy --env=prod --force=no build")),
+    vec![
+      task("deploy", vec![
+        Value::String("prod".into()),
+        Value::String("no".into())
+      ]),
+      task("build", vec![]),
+    ]
+  );
+}
+
+#[test]
+#[should_panic(
+  expected = "too few args for task 'deploy', expected 2 but got 1"
+)]
+fn panics_on_missing_args() { bind(&interpreter(), &words("deploy staging")); }
+
+#[test]
+#[should_panic(
+  expected = "too many args for task 'build', expected 0 but got 'x'"
+)]
+fn panics_on_extra_args() { bind(&interpreter(), &words("build x")); }
+
+#[test]
+#[should_panic(expected = "task 'deploy' has no parameter named 'fast'")]
+fn panics_on_unknown_flag() { bind(&interpreter(), &words("deploy --fast")); }
+
+#[test]
+#[should_panic(expected = "no task named 'x'")]
+fn panics_on_unknown_task() { bind(&interpreter(), &words("x")); }
+
+#[test]
+fn lists_tasks() {
+  let prog = Parser::new(new_lexer(
+    "# Builds\nfn build() {\n  fn inner() {}\n}\n\nfn test() {}\n\n# \
+     Deploys\n# somewhere\nfn deploy(env, force) {}\nlet x = 1"
+      .into(),
+  ))
+  .parse();
+
+  assert_eq!(list(&prog).lines().collect::<Vec<_>>(), vec![
+    "build            # Builds",
+    "test",
+    "deploy env force # Deploys",
+    "                   somewhere",
+  ]);
+}
+
+fn plan_of(source: &str, words: &str) -> Vec<String> {
+  let prog = Parser::new(new_lexer(source.into())).parse();
+  let targets = targets(&prog);
+  let mut interpreter = Interpreter::new();
+  interpreter.run(prog);
+
+  plan(&interpreter, &targets, &self::words(words))
+    .into_iter()
+    .map(|task| task.name)
+    .collect()
+}
+
+#[test]
+fn plans_deps_once() {
+  let source = "fn build() {}\nfn test(): build {}\nfn lint(): build {}\nfn \
+                install(): test, lint {}";
+
+  assert_eq!(plan_of(source, "install"), vec![
+    "build", "test", "lint", "install"
+  ]);
+  assert_eq!(plan_of(source, "build install build"), vec![
+    "build", "test", "lint", "install"
+  ]);
+}
+
+#[test]
+#[should_panic(expected = "dependency cycle between tasks: b -> c -> b")]
+fn panics_on_dep_cycle() {
+  plan_of("fn a(): b {}\nfn b(): c {}\nfn c(): b {}", "a");
+}
+
+#[test]
+#[should_panic(expected = "task 'a' depends on unknown task 'b'")]
+fn panics_on_unknown_dep() { plan_of("fn a(): b {}", "a"); }
+
+#[test]
+#[should_panic(expected = "task 'a' depends on 'b', which takes args")]
+fn panics_on_dep_with_params() { plan_of("fn a(): b {}\nfn b(x) {}", "a"); }
+
+fn touch(path: &Path, mtime: SystemTime) {
+  fs::File::options()
+    .write(true)
+    .open(path)
+    .unwrap()
+    .set_modified(mtime)
+    .unwrap();
+}
+
+#[test]
+fn skips_fresh_outputs() {
+  let dir = env::temp_dir().join(format!("koi-task-test-{}", process::id()));
+  fs::create_dir_all(&dir).unwrap();
+
+  let input = dir.join("main.c");
+  let output = dir.join("main");
+  fs::write(&input, "int main;").unwrap();
+  fs::write(&output, "").unwrap();
+
+  let path = |path: &Path| {
+    Expr::Literal(Value::String(path.to_string_lossy().into_owned()))
+  };
+  let target = Target {
+    deps:    vec![],
+    inputs:  vec![path(&dir.join("*.c"))],
+    outputs: vec![path(&output)],
+  };
+
+  let mut interpreter = Interpreter::new();
+  let mut cache = Cache::load(dir.join(CACHE_PATH));
+  let is_fresh = |interpreter: &mut Interpreter, cache: &Cache| {
+    Files::resolve(interpreter, &target).is_fresh(cache, "main")
+  };
+
+  assert!(is_fresh(&mut interpreter, &cache));
+
+  // Touching the input makes it newer, but its content is the same
+  let later = SystemTime::now() + Duration::from_secs(60);
+  cache.set("main".into(), Files::resolve(&mut interpreter, &target).hash());
+  touch(&input, later);
+  assert!(is_fresh(&mut interpreter, &Cache::load(dir.join(CACHE_PATH))));
+
+  fs::write(&input, "int main();").unwrap();
+  touch(&input, later);
+  assert!(!is_fresh(&mut interpreter, &cache));
+
+  fs::remove_file(&output).unwrap();
+  assert!(!is_fresh(&mut interpreter, &cache));
+
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+fn run_parallel_in(dir: &Path, source: &str, words: &str, keep_going: bool) {
+  let args = vec![dir.to_string_lossy().into_owned()];
+  let prog = Parser::new(new_lexer(source.into())).parse();
+  let targets = targets(&prog);
+
+  let mut interpreter = Interpreter::new();
+  interpreter.set_args(args);
+  interpreter.run(prog);
+
+  run_parallel(
+    &mut interpreter,
+    &targets,
+    &self::words(words),
+    Script { source: source.into(), import_root: None },
+    2,
+    keep_going,
+  );
+}
+
+const PARALLEL_SOURCE: &str = "let dir = args[0]
+fn mark(name) {
+  fs.write(path.join(dir, name), name)
+}
+fn a() {
+  mark('a')
+}
+fn b() {
+  mark('b')
+}
+fn c(): a, b {
+  if !fs.exists(path.join(dir, 'a')) || !fs.exists(path.join(dir, 'b')) {
+    fail()
+  }
+  mark('c')
+}
+fn bad() {
+  fail()
+}
+fn after(): bad {
+  mark('after')
+}";
+
+#[test]
+fn runs_tasks_in_parallel() {
+  let dir =
+    env::temp_dir().join(format!("koi-task-parallel-test-{}", process::id()));
+  fs::create_dir_all(&dir).unwrap();
+
+  run_parallel_in(&dir, PARALLEL_SOURCE, "c", false);
+  assert!(dir.join("c").exists());
+
+  let res = panic::catch_unwind(|| {
+    run_parallel_in(&dir, PARALLEL_SOURCE, "after", true)
+  });
+  assert!(res.is_err());
+  assert!(!dir.join("after").exists());
+
+  fs::remove_dir_all(&dir).unwrap();
+}
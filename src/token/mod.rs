@@ -7,6 +7,7 @@ pub struct Token {
 #[derive(PartialEq, Debug, Clone)]
 pub enum TokenKind {
   Identifier(String),
+  Int(i64),
   Num(f64),
   String { value: String, does_interp: bool },
 
@@ -22,6 +23,7 @@ pub enum TokenKind {
   Continue,
   Let,
   Exp,
+  WithEnv,
 
   LeftParen,
   RightParen,
@@ -51,9 +53,13 @@ pub enum TokenKind {
   Minus,
   Star,
   Slash,
+  SlashSlash,
   Caret,
   Perc,
 
+  Amper,
+  LessLess,
+
   True,
   False,
   Nil,
@@ -87,5 +93,8 @@ pub enum TokenKind {
   Space,
   Newline,
 
+  // The comment lines right above a function declaration
+  DocComment(String),
+
   UnknownChar(char),
 }
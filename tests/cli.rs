@@ -0,0 +1,148 @@
+use std::{
+  env, fs,
+  process::{self, Command, Output, Stdio},
+};
+
+fn koi(args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_koi"))
+    .args(args)
+    .env_remove("KOIX")
+    .output()
+    .expect("couldn't run koi")
+}
+
+fn stdout(output: Output) -> String {
+  String::from_utf8(output.stdout).expect("expected utf-8 output")
+}
+
+#[test]
+fn runs_inline_source() {
+  assert_eq!(stdout(koi(&["-c", "print(1 + 1)\necho ok"])), "2\nok\n");
+}
+
+#[test]
+fn passes_args_to_inline_source() {
+  assert_eq!(
+    stdout(koi(&["--command", "print(args)", "--", "a", "-b"])),
+    "['a', '-b']\n"
+  );
+}
+
+#[test]
+fn rejects_inline_source_with_path() {
+  let output = koi(&["-c", "print(1)", "script.koi"]);
+
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used"));
+}
+
+#[test]
+fn asking_for_tasks_without_koifile_fails() {
+  let dir = env::temp_dir().join(format!("koi-no-koifile-{}", process::id()));
+  fs::create_dir_all(&dir).unwrap();
+
+  for args in [&["-f", "build"][..], &["-j", "2", "-f", "build"]] {
+    let output = Command::new(env!("CARGO_BIN_EXE_koi"))
+      .args(args)
+      .current_dir(&dir)
+      .stdin(Stdio::null())
+      .output()
+      .expect("couldn't run koi");
+
+    assert!(!output.status.success());
+    assert!(
+      String::from_utf8_lossy(&output.stderr)
+        .contains("couldn't read the source file")
+    );
+  }
+
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+// With `KOIX` set, the words before `--` are the script's args and the ones
+// after it are Koi's own flags
+fn koix(mode: &str, args: &[&str], dir: Option<&str>) -> Output {
+  let mut cmd = Command::new(env!("CARGO_BIN_EXE_koi"));
+  if let Some(dir) = dir {
+    cmd.current_dir(dir);
+  }
+
+  cmd.args(args).env("KOIX", mode).output().expect("couldn't run koi")
+}
+
+#[test]
+fn koix_passes_every_flag_after_separator() {
+  assert_eq!(
+    stdout(koix("1", &["a", "b", "--", "-c", "print(args)"], None)),
+    "['a', 'b']\n"
+  );
+}
+
+#[test]
+fn koix_make_runs_koifile_tasks() {
+  let dir = env::temp_dir().join(format!("koi-koix-{}", std::process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  fs::write(
+    dir.join("Koifile"),
+    "fn all() { print('all') }\nfn greet(name) { print('hi ' + name) }",
+  )
+  .unwrap();
+
+  let dir_str = dir.to_str().unwrap();
+  let default = stdout(koix("make", &[], Some(dir_str)));
+  let greet = stdout(koix("make", &["greet", "koi"], Some(dir_str)));
+  fs::remove_dir_all(&dir).unwrap();
+
+  assert_eq!(default, "all\n");
+  assert_eq!(greet, "hi koi\n");
+}
+
+#[test]
+fn parallel_tasks_run_top_level_once() {
+  let source = "let n = 41
+echo top
+fn show(x) {
+  print(x + n)
+}
+fn a() {
+  show(1)
+  sh -c 'echo err a >&2'
+}
+fn b() {
+  show(2)
+}";
+  let output = koi(&["-j", "2", "-c", source, "-f", "a", "b"]);
+  let out = String::from_utf8(output.stdout).unwrap();
+  let err = String::from_utf8(output.stderr).unwrap();
+
+  assert!(output.status.success(), "{}", err);
+  assert_eq!(out.matches("top").count(), 1);
+  assert!(out.contains("[a] 42\n") && out.contains("[b] 43\n"));
+  assert!(err.contains("[a] err a\n"));
+}
+
+#[test]
+fn parallel_tasks_set_up_values_that_cant_be_copied() {
+  let source = "let r = 0..2\nlet double = fn(x) {\n  return x * 2\n}
+fn a() {\n  print(double(2))\n  print([1, 2, 3][r])\n}";
+  let output = koi(&["-j", "2", "-c", source, "-f", "a"]);
+  assert_eq!(stdout(output), "[a] 4\n[a] [1, 2]\n");
+
+  let source = "let r = nil\nr = 0..2\nfn a() {\n  print(r)\n}";
+  let output = koi(&["-j", "2", "-c", source, "-f", "a"]);
+  assert!(!output.status.success());
+  assert!(
+    String::from_utf8_lossy(&output.stderr)
+      .contains("task 'a' failed: can't copy 'r' to other threads")
+  );
+}
+
+#[test]
+fn parallel_tasks_drain_large_output() {
+  let source = "fn b() {\n  seq 1 30000\n  sh -c 'seq 1 30000 >&2'\n}";
+  let output = koi(&["-j", "2", "-c", source, "-f", "b"]);
+
+  assert!(output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).ends_with("[b] 30000\n"));
+  assert!(stdout(output).ends_with("[b] 30000\n"));
+}
@@ -0,0 +1,117 @@
+use {
+  koi::{indexmap::IndexMap, FromValue, Interpreter, Value},
+  std::{cell::Cell, env, fs, path::PathBuf, process, rc::Rc},
+};
+
+fn module_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+  let dir = env::temp_dir().join(format!("koi-{}-{}", name, process::id()));
+  fs::create_dir_all(&dir).unwrap();
+
+  for (file, source) in files {
+    fs::write(dir.join(file), source).unwrap();
+  }
+
+  dir
+}
+
+#[test]
+fn embeds_scripts() {
+  let mut interpreter = Interpreter::new();
+  interpreter.do_collect();
+
+  interpreter.set_var("greeting", Value::String("hi".to_string()));
+  interpreter.run_source("fn greet(name) {\n  print('{greeting} {name}')\n}");
+  interpreter.call_fn("greet", vec![Value::String("koi".to_string())]);
+
+  assert_eq!(interpreter.take_collected(), "hi koi\n");
+  assert_eq!(interpreter.eval_source("greeting.len() * 2"), Value::Int(4));
+}
+
+#[test]
+fn runs_files_with_imports_relative_to_them() {
+  let dir = module_dir("run-file", &[
+    ("util.koi", "exp let name = 'util'"),
+    ("main.koi", "import 'util' as util\nprint(util.name)"),
+  ]);
+
+  let mut interpreter = Interpreter::new();
+  interpreter.do_collect();
+  interpreter.run_file(dir.join("main.koi"));
+
+  assert_eq!(interpreter.take_collected(), "util\n");
+
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn host_fns_keep_state() {
+  let count = Rc::new(Cell::new(0));
+  let mut interpreter = Interpreter::new();
+  interpreter.do_collect();
+
+  let counter = Rc::clone(&count);
+  interpreter.register_fn("tick", Some(0), move |_| {
+    counter.set(counter.get() + 1);
+    counter.get()
+  });
+  interpreter.run_source("tick()\nprint(tick())");
+
+  assert_eq!(interpreter.take_collected(), "2\n");
+  assert_eq!(count.get(), 2);
+
+  // Modules see host functions as well
+  let dir = module_dir("host-fns", &[
+    ("ticks.koi", "exp let last = tick()"),
+    ("main.koi", "import 'ticks' as ticks\nprint(ticks.last)"),
+  ]);
+  interpreter.run_file(dir.join("main.koi"));
+
+  assert_eq!(interpreter.take_collected(), "3\n");
+
+  fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn converts_host_fn_values() {
+  let mut interpreter = Interpreter::new();
+  interpreter.do_collect();
+
+  interpreter.register_fn("total", Some(1), |mut args| {
+    Vec::<f64>::from_value(args.remove(0)).into_iter().sum::<f64>()
+  });
+  interpreter.register_fn("labels", Some(1), |mut args| {
+    IndexMap::<String, Option<String>>::from_value(args.remove(0))
+      .into_iter()
+      .map(|(key, val)| format!("{}={}", key, val.unwrap_or_default()))
+      .collect::<Vec<_>>()
+  });
+  interpreter.run_source(
+    "print(total([1, 2.5]))\nprint(labels({app: 'koi', tier: nil}))",
+  );
+
+  assert_eq!(interpreter.take_collected(), "3.5\n['app=koi', 'tier=']\n");
+}
+
+#[test]
+#[should_panic(expected = "expected string, got int")]
+fn panics_on_host_fn_arg_mismatch() {
+  let mut interpreter = Interpreter::new();
+  interpreter.register_fn("shout", Some(1), |mut args| {
+    String::from_value(args.remove(0)).to_uppercase()
+  });
+  interpreter.run_source("shout(1)");
+}
+
+#[test]
+fn returns_errors_and_recovers() {
+  let mut interpreter = Interpreter::new();
+
+  assert_eq!(interpreter.try_run("let x = 1"), Ok(()));
+  assert_eq!(
+    interpreter.try_run("let y = 2\nexit('x')"),
+    Err("expected integer".to_string())
+  );
+  assert_eq!(interpreter.try_eval("x + 1"), Ok(Value::Int(2)));
+  assert_eq!(interpreter.try_eval("y"), Ok(Value::Nil));
+  assert!(interpreter.try_eval("x +").is_err());
+}